@@ -0,0 +1,95 @@
+//! Compares the lexer against the regex splitter it replaced.
+//!
+//! `cargo bench --bench split_args [-- <trace file>]`; without a file a
+//! synthetic trace of 50k lines is used.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+use strace_parse::{lexer, parse_line, Context};
+
+/// The pattern `split_args` used before the lexer.
+const RE: &str = "((?:\"[^\"\n]*\"|<[^>\n]*>|\\{[^}\n]*\\}|[^,\n])+)";
+
+fn regex_split_args(s: &str) -> Vec<String> {
+    let re = Regex::new(RE).unwrap();
+    re.captures_iter(s).map(|cap| cap[1].to_string()).collect()
+}
+
+fn regex_split_args_cached(re: &Regex, s: &str) -> Vec<String> {
+    re.captures_iter(s).map(|cap| cap[1].to_string()).collect()
+}
+
+fn synthetic_trace(n: usize) -> Vec<String> {
+    let templates = [
+        "{pid} openat(AT_FDCWD</home/user/project>, \"src/module_{i}.rs\", O_RDONLY|O_CLOEXEC) = 3</home/user/project/src/module_{i}.rs>",
+        "{pid} newfstatat(3</home/user/project/src/module_{i}.rs>, \"\", {st_mode=S_IFREG|0644, st_size={i}, ...}, AT_EMPTY_PATH) = 0",
+        "{pid} read(3</home/user/project/src/module_{i}.rs>, \"fn main() {{ println!(\\\"hi, there\\\"); }}\\n\"..., 8192) = 8192",
+        "{pid} execve(\"/usr/bin/cc\", [\"cc\", \"-c\", \"file_{i}.c\", \"-o\", \"file_{i}.o\"], 0x7ffd1234 /* 42 vars */) = 0",
+        "{pid} unlinkat(AT_FDCWD</tmp>, \"/tmp/cc{i}.s\", 0) = 0",
+        "{pid} clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|CLONE_CHILD_SETTID|SIGCHLD, child_tidptr=0x7f12) = {child}",
+    ];
+    (0..n)
+        .map(|i| {
+            templates[i % templates.len()]
+                .replace("{pid}", &(1000 + i % 7).to_string())
+                .replace("{child}", &(5000 + i).to_string())
+                .replace("{i}", &i.to_string())
+        })
+        .collect()
+}
+
+fn args_of(line: &str) -> &str {
+    lexer::split_call(lexer::split_pid(line).unwrap().1).map_or("", |c| c.1)
+}
+
+fn time<F: FnMut()>(name: &str, lines: usize, mut f: F) -> Duration {
+    let start = Instant::now();
+    f();
+    let elapsed = start.elapsed();
+    let per_line = elapsed.as_nanos() as f64 / lines as f64;
+    println!("{name:<28} {:>10.2?} {per_line:>10.1} ns/line", elapsed);
+    elapsed
+}
+
+fn main() {
+    let lines = match std::env::args().skip(1).find(|a| !a.starts_with('-')) {
+        Some(path) => std::fs::read_to_string(path)
+            .expect("cannot read trace")
+            .lines()
+            .map(str::to_string)
+            .collect(),
+        None => synthetic_trace(50_000),
+    };
+    let args: Vec<&str> = lines.iter().map(|l| args_of(l)).collect();
+    println!("{} lines", lines.len());
+
+    let regex = time("regex split (per call)", args.len(), || {
+        for a in &args {
+            black_box(regex_split_args(a));
+        }
+    });
+    let re = Regex::new(RE).unwrap();
+    time("regex split (compiled once)", args.len(), || {
+        for a in &args {
+            black_box(regex_split_args_cached(&re, a));
+        }
+    });
+    let lexer = time("lexer split", args.len(), || {
+        for a in &args {
+            black_box(lexer::split_args(a));
+        }
+    });
+    time("parse_line end to end", lines.len(), || {
+        let mut ctx = Context::new();
+        ctx.set_dir("/", None);
+        for l in &lines {
+            let _ = black_box(parse_line(l, &mut ctx));
+        }
+    });
+    println!(
+        "lexer is {:.1}x faster than the old regex path",
+        regex.as_secs_f64() / lexer.as_secs_f64()
+    );
+}
@@ -0,0 +1,447 @@
+//! Typed syntax tree for strace's rendering of syscall arguments.
+//!
+//! ```text
+//! openat(AT_FDCWD</home/u>, "foo", O_RDONLY|O_CLOEXEC)
+//!        ^ Fd              ^ Str  ^ Flags
+//! execve("/bin/ls", ["ls", "-l"], 0x7ffd /* 20 vars */)
+//!                   ^ Array       ^ Commented(Int)
+//! clone(child_stack=NULL, flags=CLONE_VM|SIGCHLD)
+//!       ^ Named(Null)     ^ Named(Flags)
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::lexer::{split_args, trailing_comment};
+
+/// The fd number of an [`Arg::Fd`]; `AT_FDCWD` is kept symbolic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FdNum {
+    AtFdcwd,
+    Num(i32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg<'a> {
+    /// A quoted string, unescaped. `truncated` if strace cut it short (`"..."...`).
+    Str { value: String, truncated: bool },
+    Int(i64),
+    /// `|`-separated names and numbers, e.g. `O_WRONLY|O_CREAT|0x80000`.
+    /// A lone identifier such as `SIGCHLD` is a one-element set.
+    Flags(Vec<&'a str>),
+    /// An fd with its `-y` annotation (`3</etc/passwd>`), unescaped, or a
+    /// bare `AT_FDCWD`.
+    Fd { fd: FdNum, path: Option<Cow<'a, str>> },
+    /// `{name=value, ...}`; unnamed fields have no name.
+    Struct(Vec<(Option<&'a str>, Arg<'a>)>),
+    Array(Vec<Arg<'a>>),
+    /// `name=value` at the top level, as in `clone(flags=...)`.
+    Named(&'a str, Box<Arg<'a>>),
+    /// A `/* ... */` comment on its own.
+    Comment(&'a str),
+    /// A value followed by a comment, e.g. `0x7ffd /* 20 vars */`.
+    Commented(Box<Arg<'a>>, &'a str),
+    /// The `...` strace prints for elided struct fields or array entries.
+    Truncated,
+    Null,
+    /// Anything else (pointers with offsets, `makedev(...)`, sigsets), verbatim.
+    Other(&'a str),
+}
+
+impl<'a> Arg<'a> {
+    /// The string value, with `NULL` read as the empty string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Arg::Str { value, .. } => Some(value),
+            Arg::Null => Some(""),
+            Arg::Commented(a, _) => a.as_str(),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Arg::Int(i) => Some(*i),
+            Arg::Fd { fd: FdNum::Num(n), .. } => Some(*n as i64),
+            Arg::Commented(a, _) => a.as_int(),
+            _ => None,
+        }
+    }
+
+    /// Path from the `-y` annotation of an fd argument or return value.
+    pub fn fd_path(&self) -> Option<&str> {
+        match self {
+            Arg::Fd { path, .. } => path.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn fd(&self) -> Option<FdNum> {
+        match self {
+            Arg::Fd { fd, .. } => Some(*fd),
+            Arg::Int(i) => i32::try_from(*i).ok().map(FdNum::Num),
+            _ => None,
+        }
+    }
+
+    /// Elements of a flag set, including the value of `flags=...`.
+    pub fn flags(&self) -> Option<&[&'a str]> {
+        match self {
+            Arg::Flags(f) => Some(f),
+            Arg::Named(_, a) => a.flags(),
+            _ => None,
+        }
+    }
+
+    pub fn has_flag(&self, name: &str) -> bool {
+        self.flags().is_some_and(|f| f.contains(&name))
+    }
+
+    /// Field `name` of a struct.
+    pub fn field(&self, name: &str) -> Option<&Arg<'a>> {
+        match self {
+            Arg::Struct(fields) => fields
+                .iter()
+                .find(|(n, _)| *n == Some(name))
+                .map(|(_, a)| a),
+            _ => None,
+        }
+    }
+
+    /// The value of a `name=value` argument.
+    pub fn named(&self, name: &str) -> Option<&Arg<'a>> {
+        match self {
+            Arg::Named(n, a) if *n == name => Some(a),
+            _ => None,
+        }
+    }
+}
+
+/// End of the run of at most `max` bytes from `start` that satisfy `f`.
+fn digits_end(b: &[u8], start: usize, max: usize, f: impl Fn(u8) -> bool) -> usize {
+    let limit = b.len().min(start + max);
+    (start..limit).find(|&j| !f(b[j])).unwrap_or(limit)
+}
+
+/// Undo strace's string escaping (`\n`, `\t`, `\"`, `\\`, octal and `\x` bytes).
+fn unescape(s: &str) -> String {
+    let b = s.as_bytes();
+    let mut out = Vec::with_capacity(b.len());
+    let mut i = 0;
+    while i < b.len() {
+        if b[i] != b'\\' || i + 1 == b.len() {
+            out.push(b[i]);
+            i += 1;
+            continue;
+        }
+        i += 1;
+        let c = b[i];
+        i += 1;
+        match c {
+            b'n' => out.push(b'\n'),
+            b't' => out.push(b'\t'),
+            b'r' => out.push(b'\r'),
+            b'v' => out.push(0x0b),
+            b'f' => out.push(0x0c),
+            b'x' => {
+                let end = digits_end(b, i, 2, |c| c.is_ascii_hexdigit());
+                match u8::from_str_radix(&s[i..end], 16) {
+                    Ok(v) => out.push(v),
+                    Err(_) => out.push(b'x'),
+                }
+                i = end;
+            }
+            b'0'..=b'7' => {
+                let start = i - 1;
+                let end = digits_end(b, start, 3, |c| (b'0'..=b'7').contains(&c));
+                out.push(u8::from_str_radix(&s[start..end], 8).unwrap_or(0));
+                i = end;
+            }
+            c => out.push(c),
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn parse_int(s: &str) -> Option<i64> {
+    let (neg, digits) = match s.strip_prefix('-') {
+        Some(d) => (true, d),
+        None => (false, s),
+    };
+    let v = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        i64::from_str_radix(&digits[1..], 8).ok()?
+    } else if !digits.is_empty() && digits.bytes().all(|c| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if neg { -v } else { v })
+}
+
+fn is_ident(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'_')
+}
+
+fn is_flag_item(s: &str) -> bool {
+    is_ident(s) || parse_int(s).is_some()
+}
+
+fn parse_fd(s: &str) -> Option<Arg<'_>> {
+    let lt = s.find('<')?;
+    let path = s[lt + 1..].strip_suffix('>')?;
+    let fd = match &s[..lt] {
+        "AT_FDCWD" => FdNum::AtFdcwd,
+        n => FdNum::Num(n.parse().ok()?),
+    };
+    let path = if path.contains('\\') { Cow::Owned(unescape(path)) } else { Cow::Borrowed(path) };
+    Some(Arg::Fd { fd, path: Some(path) })
+}
+
+/// Parse one argument (already split at top-level commas).
+pub fn parse_arg(s: &str) -> Arg<'_> {
+    let s = s.trim();
+    if let Some(start) = trailing_comment(s) {
+        let comment = s[start + 2..s.len() - 2].trim();
+        let value = s[..start].trim();
+        if value.is_empty() {
+            return Arg::Comment(comment);
+        }
+        return Arg::Commented(Box::new(parse_arg(value)), comment);
+    }
+    if s == "..." {
+        return Arg::Truncated;
+    }
+    if s == "NULL" {
+        return Arg::Null;
+    }
+    if s == "AT_FDCWD" {
+        return Arg::Fd { fd: FdNum::AtFdcwd, path: None };
+    }
+    if let Some(body) = s.strip_prefix('"') {
+        let (body, truncated) = match body.strip_suffix("...") {
+            Some(b) => (b, true),
+            None => (body, false),
+        };
+        if let Some(body) = body.strip_suffix('"') {
+            return Arg::Str { value: unescape(body), truncated };
+        }
+        return Arg::Other(s);
+    }
+    if let Some(inner) = s.strip_prefix('{').and_then(|r| r.strip_suffix('}')) {
+        let fields = split_args(inner)
+            .into_iter()
+            .map(|f| match split_name(f) {
+                Some((name, value)) => (Some(name), parse_arg(value)),
+                None => (None, parse_arg(f)),
+            })
+            .collect();
+        return Arg::Struct(fields);
+    }
+    if let Some(inner) = s.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+        return Arg::Array(split_args(inner).into_iter().map(parse_arg).collect());
+    }
+    if let Some(fd) = parse_fd(s) {
+        return fd;
+    }
+    if let Some(i) = parse_int(s) {
+        return Arg::Int(i);
+    }
+    if let Some((name, value)) = split_name(s) {
+        return Arg::Named(name, Box::new(parse_arg(value)));
+    }
+    if s.split('|').all(is_flag_item) {
+        return Arg::Flags(s.split('|').collect());
+    }
+    Arg::Other(s)
+}
+
+/// `name=value` with an identifier name.
+fn split_name(s: &str) -> Option<(&str, &str)> {
+    let (name, value) = s.split_once('=')?;
+    is_ident(name).then_some((name, value))
+}
+
+/// Parse a whole argument list.
+pub fn parse_args(s: &str) -> Vec<Arg<'_>> {
+    split_args(s).into_iter().map(parse_arg).collect()
+}
+
+fn write_escaped(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => write!(f, "\\{c}")?,
+            '\n' => f.write_str("\\n")?,
+            '\t' => f.write_str("\\t")?,
+            c if c.is_control() => write!(f, "\\{:o}", c as u32)?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    Ok(())
+}
+
+/// strace's `-y` escaping: octal for `<`, `>` and unprintable bytes.
+fn write_annotation(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    for c in s.chars() {
+        match c {
+            '\\' => f.write_str("\\\\")?,
+            c if matches!(c, '<' | '>') || c.is_control() => write!(f, "\\{:o}", c as u32)?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    Ok(())
+}
+
+fn write_list<T: fmt::Display>(f: &mut fmt::Formatter<'_>, items: impl IntoIterator<Item = T>) -> fmt::Result {
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{item}")?;
+    }
+    Ok(())
+}
+
+/// Renders back in strace's syntax (numbers in decimal).
+impl fmt::Display for Arg<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arg::Str { value, truncated } => {
+                f.write_str("\"")?;
+                write_escaped(f, value)?;
+                f.write_str(if *truncated { "\"..." } else { "\"" })
+            }
+            Arg::Int(i) => write!(f, "{i}"),
+            Arg::Flags(flags) => f.write_str(&flags.join("|")),
+            Arg::Fd { fd, path } => {
+                match fd {
+                    FdNum::AtFdcwd => f.write_str("AT_FDCWD")?,
+                    FdNum::Num(n) => write!(f, "{n}")?,
+                }
+                match path {
+                    Some(p) => {
+                        f.write_str("<")?;
+                        write_annotation(f, p)?;
+                        f.write_str(">")
+                    }
+                    None => Ok(()),
+                }
+            }
+            Arg::Struct(fields) => {
+                f.write_str("{")?;
+                write_list(f, fields.iter().map(|(name, a)| match name {
+                    Some(n) => format!("{n}={a}"),
+                    None => a.to_string(),
+                }))?;
+                f.write_str("}")
+            }
+            Arg::Array(items) => {
+                f.write_str("[")?;
+                write_list(f, items)?;
+                f.write_str("]")
+            }
+            Arg::Named(n, a) => write!(f, "{n}={a}"),
+            Arg::Comment(c) => write!(f, "/* {c} */"),
+            Arg::Commented(a, c) => write!(f, "{a} /* {c} */"),
+            Arg::Truncated => f.write_str("..."),
+            Arg::Null => f.write_str("NULL"),
+            Arg::Other(s) => f.write_str(s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn str_arg(value: &str) -> Arg<'static> {
+        Arg::Str { value: value.to_string(), truncated: false }
+    }
+
+    #[test]
+    fn strings_are_unescaped() {
+        assert_eq!(parse_arg(r#""a\"b\\c\n""#), str_arg("a\"b\\c\n"));
+        assert_eq!(parse_arg(r#""\303\251\x41\0""#), str_arg("éA\0"));
+        assert_eq!(parse_arg(r#""abc"..."#), Arg::Str { value: "abc".to_string(), truncated: true });
+    }
+
+    #[test]
+    fn unterminated_string_is_kept_verbatim() {
+        assert_eq!(parse_arg(r#""abc"#), Arg::Other(r#""abc"#));
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(parse_arg("42"), Arg::Int(42));
+        assert_eq!(parse_arg("-1"), Arg::Int(-1));
+        assert_eq!(parse_arg("0x1f"), Arg::Int(31));
+        assert_eq!(parse_arg("0644"), Arg::Int(0o644));
+    }
+
+    #[test]
+    fn flags_and_named() {
+        assert_eq!(parse_arg("O_WRONLY|O_CREAT|0x80000"), Arg::Flags(vec!["O_WRONLY", "O_CREAT", "0x80000"]));
+        let clone = parse_arg("flags=CLONE_VM|SIGCHLD");
+        assert!(clone.has_flag("CLONE_VM"));
+        assert_eq!(clone.named("flags").and_then(Arg::flags), Some(&["CLONE_VM", "SIGCHLD"][..]));
+    }
+
+    #[test]
+    fn nested_structs_and_arrays() {
+        let a = parse_arg(r#"{st_mode=S_IFREG|0644, st_size=[1, {x="a, b"}], ...}"#);
+        assert_eq!(a.field("st_mode"), Some(&Arg::Flags(vec!["S_IFREG", "0644"])));
+        let Some(Arg::Array(items)) = a.field("st_size") else {
+            panic!("expected an array: {a:?}");
+        };
+        assert_eq!(items[0], Arg::Int(1));
+        assert_eq!(items[1].field("x"), Some(&str_arg("a, b")));
+        let Arg::Struct(fields) = &a else { unreachable!() };
+        assert_eq!(fields.last(), Some(&(None, Arg::Truncated)));
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(parse_arg("/* 20 vars */"), Arg::Comment("20 vars"));
+        assert_eq!(parse_arg("0x7ffd /* 20 vars */"), Arg::Commented(Box::new(Arg::Int(0x7ffd)), "20 vars"));
+        assert_eq!(parse_arg(r#""/* x */""#), str_arg("/* x */"));
+    }
+
+    #[test]
+    fn fd_annotations() {
+        let fd = parse_arg("3</etc/passwd>");
+        assert_eq!(fd.fd(), Some(FdNum::Num(3)));
+        assert_eq!(fd.fd_path(), Some("/etc/passwd"));
+        assert!(matches!(fd, Arg::Fd { path: Some(Cow::Borrowed(_)), .. }));
+
+        let cwd = parse_arg("AT_FDCWD</home/u>");
+        assert_eq!(cwd.fd(), Some(FdNum::AtFdcwd));
+        assert_eq!(cwd.fd_path(), Some("/home/u"));
+        assert_eq!(parse_arg("AT_FDCWD"), Arg::Fd { fd: FdNum::AtFdcwd, path: None });
+    }
+
+    #[test]
+    fn fd_annotations_are_unescaped_like_strings() {
+        let fd = parse_arg(r"3</a/\303\251>");
+        assert_eq!(fd.fd_path(), parse_arg(r#""/a/\303\251""#).as_str());
+        assert_eq!(parse_arg(r"4</a/b\76c\74d>").fd_path(), Some("/a/b>c<d"));
+        assert_eq!(parse_arg(r"5</a\\b>").fd_path(), Some(r"/a\b"));
+    }
+
+    #[test]
+    fn fd_annotations_render_escaped() {
+        for s in ["3</etc/passwd>", r"4</a/b\76c\74d>", r"5</a\\b>", "AT_FDCWD</w>"] {
+            assert_eq!(parse_arg(s).to_string(), s);
+        }
+    }
+
+    #[test]
+    fn whole_argument_list() {
+        let args = parse_args(r#"AT_FDCWD</w>, "a\"b, c", O_RDONLY|O_CLOEXEC"#);
+        assert_eq!(args.len(), 3);
+        assert_eq!(args[1].as_str(), Some("a\"b, c"));
+        assert!(args[2].has_flag("O_CLOEXEC"));
+        assert_eq!(parse_arg("NULL").as_str(), Some(""));
+    }
+}
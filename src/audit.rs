@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::context::Context;
+use crate::error::ParseError;
+use crate::parse::{parse_record, Record, SyscallLine};
+
+/// One syscall name the parser has no rule for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnknownSyscall {
+    pub count: usize,
+    /// Trace line of the first call.
+    pub first_line: usize,
+    /// The first call, as `pid name(args) = ret`.
+    pub example: String,
+}
+
+/// Syscalls that are in no classification table and not in
+/// [`crate::IGNORE_SET`]. Whatever files they touched are missing from the
+/// read and write sets, so an empty audit is what makes the sets
+/// trustworthy. Serializes as a map from name to [`UnknownSyscall`].
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+#[serde(transparent)]
+pub struct SyscallAudit {
+    syscalls: BTreeMap<String, UnknownSyscall>,
+}
+
+impl SyscallAudit {
+    pub fn new() -> SyscallAudit {
+        SyscallAudit::default()
+    }
+
+    /// Count an unclassified call.
+    pub fn add(&mut self, call: &SyscallLine) {
+        self.syscalls
+            .entry(call.syscall.clone())
+            .and_modify(|u| u.count += 1)
+            .or_insert_with(|| UnknownSyscall { count: 1, first_line: call.line, example: call.to_string() });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.syscalls.is_empty()
+    }
+
+    pub fn get(&self, syscall: &str) -> Option<&UnknownSyscall> {
+        self.syscalls.get(syscall)
+    }
+
+    /// Unclassified calls of all names.
+    pub fn total(&self) -> usize {
+        self.syscalls.values().map(|u| u.count).sum()
+    }
+
+    /// By name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &UnknownSyscall)> {
+        self.syscalls.iter().map(|(name, u)| (name.as_str(), u))
+    }
+
+    /// Most frequent first, ties by name.
+    pub fn by_count(&self) -> Vec<(&str, &UnknownSyscall)> {
+        let mut all: Vec<_> = self.iter().collect();
+        all.sort_by(|a, b| b.1.count.cmp(&a.1.count).then(a.0.cmp(b.0)));
+        all
+    }
+}
+
+/// Audit a whole trace for unclassified syscalls. Bad lines are handled
+/// according to the context's [`crate::ErrorMode`].
+pub fn audit_syscalls<S: AsRef<str>>(trace_object: &[S], ctx: &mut Context) -> Result<SyscallAudit, ParseError> {
+    let mut audit = SyscallAudit::new();
+    for l in trace_object {
+        match parse_record(l.as_ref(), ctx) {
+            Ok(Record::Call { call, files: None }) => audit.add(&call),
+            Ok(_) => {}
+            Err(e) => ctx.report(e)?,
+        }
+    }
+    Ok(audit)
+}
@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+
+use crate::error::{ErrorMode, ParseError, ParseErrorKind};
+use crate::flags::CloneFlags;
+use crate::parse::{ExitStatus, Signal};
+use crate::process::{Exec, ProcessTree};
+use crate::table::SyscallTable;
+
+/// Most errors [`Context::diagnostics`] keeps; later ones are only counted.
+pub const MAX_DIAGNOSTICS: usize = 1000;
+
+/// Per-trace state carried between lines: pending `<unfinished ...>` calls,
+/// the process tree with each pid's working directory, and the open fds of
+/// each process and which pids share an fd table.
+#[derive(Debug, Default)]
+pub struct Context {
+    line_dict: HashMap<i32, String>,
+    procs: ProcessTree,
+    fd_dict: HashMap<i32, HashMap<i32, String>>,
+    fd_group_dict: HashMap<i32, i32>,
+    pub(crate) line_no: usize,
+    error_mode: ErrorMode,
+    diagnostics: Vec<ParseError>,
+    dropped_diagnostics: usize,
+    table: SyscallTable,
+}
+
+impl Context {
+    pub fn new() -> Context {
+        let line_dict = HashMap::new();
+        Context {
+            line_dict,
+            procs: ProcessTree::new(),
+            fd_dict: HashMap::new(),
+            fd_group_dict: HashMap::new(),
+            line_no: 0,
+            error_mode: ErrorMode::default(),
+            diagnostics: Vec::new(),
+            dropped_diagnostics: 0,
+            table: SyscallTable::new(),
+        }
+    }
+
+    /// Number of lines parsed so far.
+    pub fn line_no(&self) -> usize {
+        self.line_no
+    }
+
+    pub fn error_mode(&self) -> ErrorMode {
+        self.error_mode
+    }
+
+    pub fn set_error_mode(&mut self, mode: ErrorMode) {
+        self.error_mode = mode;
+    }
+
+    /// Rules that take precedence over the built-in classification.
+    pub fn syscall_table(&self) -> &SyscallTable {
+        &self.table
+    }
+
+    pub fn set_syscall_table(&mut self, table: SyscallTable) {
+        self.table = table;
+    }
+
+    /// The first [`MAX_DIAGNOSTICS`] errors skipped over in
+    /// [`ErrorMode::Lenient`].
+    pub fn diagnostics(&self) -> &[ParseError] {
+        &self.diagnostics
+    }
+
+    /// Errors skipped over but not kept, past the first [`MAX_DIAGNOSTICS`].
+    pub fn dropped_diagnostics(&self) -> usize {
+        self.dropped_diagnostics
+    }
+
+    /// Strict mode hands the error back; lenient mode records it and
+    /// returns `Ok` so the caller can move on to the next line.
+    pub fn report(&mut self, err: ParseError) -> Result<(), ParseError> {
+        match self.error_mode {
+            ErrorMode::Strict => Err(err),
+            ErrorMode::Lenient => {
+                if self.diagnostics.len() < MAX_DIAGNOSTICS {
+                    self.diagnostics.push(err);
+                } else {
+                    self.dropped_diagnostics += 1;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// `parent` created `child` (`clone`, `clone3`, `fork`, `vfork`).
+    pub fn clone_process(&mut self, parent: i32, child: i32, flags: CloneFlags) {
+        self.procs.clone_process(parent, child, flags);
+    }
+
+    /// A successful `execve`/`execveat` of `exe`.
+    pub fn exec(&mut self, pid: i32, exe: &str, argv: Vec<String>) {
+        self.procs.exec(pid, Exec { exe: exe.to_string(), argv });
+    }
+
+    /// `pid` went away, as its `+++` line or the tracer reported.
+    pub fn process_exited(&mut self, pid: i32, status: ExitStatus) {
+        self.procs.exited(pid, status);
+    }
+
+    /// Thread `tid` of `leader`'s group called `execve`, which ended every
+    /// other thread and gave `tid` the leader's pid. What `tid` was doing,
+    /// including the `execve` itself if it is still unfinished, carries on
+    /// under `leader`.
+    pub fn thread_exec(&mut self, leader: i32, tid: i32) {
+        if let Some(line) = self.line_dict.remove(&tid) {
+            self.line_dict.insert(leader, line);
+        }
+        let owner = self.fd_owner(tid);
+        if owner != self.fd_owner(leader) {
+            let table = self.fd_dict.get(&owner).cloned().unwrap_or_default();
+            self.fd_group_dict.remove(&leader);
+            self.fd_dict.insert(leader, table);
+        }
+        self.procs.thread_exec(leader, tid);
+    }
+
+    /// `signal` was delivered to `pid`.
+    pub fn signal_received(&mut self, pid: i32, signal: Signal) {
+        self.procs.signal(pid, signal);
+    }
+
+    /// Make sure `pid` is in the process tree, even if it never forks,
+    /// execs or changes directory.
+    pub(crate) fn saw_pid(&mut self, pid: i32) {
+        if self.procs.get(pid).is_none()
+            && let Some(parent) = self.pending_clone()
+        {
+            self.procs.add_early_child(parent, pid);
+        }
+        self.procs.add(pid);
+    }
+
+    /// The one pid waiting for a `clone`/`fork` to return, which a pid
+    /// seen for the first time is then most likely the child of. `None`
+    /// if there are none, or several to choose from.
+    fn pending_clone(&self) -> Option<i32> {
+        let mut callers = self
+            .line_dict
+            .iter()
+            .filter(|(_, l)| ["clone(", "clone3(", "fork(", "vfork("].iter().any(|c| l.starts_with(c)))
+            .map(|(&pid, _)| pid);
+        match (callers.next(), callers.next()) {
+            (Some(pid), None) => Some(pid),
+            _ => None,
+        }
+    }
+
+    /// `unshare(CLONE_FS)`.
+    pub fn unshare_fs(&mut self, pid: i32) {
+        self.procs.unshare_fs(pid);
+    }
+
+    pub fn process_tree(&self) -> &ProcessTree {
+        &self.procs
+    }
+
+    /// Set the working directory of `pid`, or with no pid the one every
+    /// pid not yet seen starts in.
+    pub fn set_dir(&mut self, path: &str, pid: Option<i32>) {
+        match pid {
+            Some(pid) => self.procs.set_cwd(pid, path),
+            None => self.procs.set_initial_cwd(path),
+        }
+    }
+
+    pub fn get_dir(&self, pid: i32) -> String {
+        self.procs.cwd(pid).to_string()
+    }
+
+    /// The pid whose fd table `pid` uses (itself unless it was cloned with
+    /// `CLONE_FILES`).
+    fn fd_owner(&self, pid: i32) -> i32 {
+        let mut pid = pid;
+        while let Some(&owner) = self.fd_group_dict.get(&pid) {
+            if owner == pid {
+                break;
+            }
+            pid = owner;
+        }
+        pid
+    }
+
+    /// Path open on `fd` in `pid`, if the trace showed where it came from.
+    pub fn get_fd(&self, pid: i32, fd: i32) -> Option<&str> {
+        self.fd_dict.get(&self.fd_owner(pid))?.get(&fd).map(String::as_str)
+    }
+
+    pub fn set_fd(&mut self, pid: i32, fd: i32, path: &str) {
+        let owner = self.fd_owner(pid);
+        self.fd_dict.entry(owner).or_default().insert(fd, path.to_string());
+    }
+
+    pub fn close_fd(&mut self, pid: i32, fd: i32) {
+        let owner = self.fd_owner(pid);
+        if let Some(table) = self.fd_dict.get_mut(&owner) {
+            table.remove(&fd);
+        }
+    }
+
+    /// `close_range(first, last)`.
+    pub fn close_fds(&mut self, pid: i32, first: i32, last: i32) {
+        let owner = self.fd_owner(pid);
+        if let Some(table) = self.fd_dict.get_mut(&owner) {
+            table.retain(|fd, _| !(first..=last).contains(fd));
+        }
+    }
+
+    /// `new` now refers to whatever `old` did; if `old` is unknown, so is `new`.
+    pub fn dup_fd(&mut self, pid: i32, old: i32, new: i32) {
+        match self.get_fd(pid, old).map(str::to_string) {
+            Some(path) => self.set_fd(pid, new, &path),
+            None => self.close_fd(pid, new),
+        }
+    }
+
+    /// Give `child` the parent's fds: the same table with `CLONE_FILES`,
+    /// otherwise a copy. Fds the child opened before the clone returned in
+    /// the trace are kept on top.
+    pub fn clone_fds(&mut self, parent: i32, child: i32, share: bool) {
+        let owner = self.fd_owner(parent);
+        let own = if self.fd_group_dict.contains_key(&child) {
+            HashMap::new()
+        } else {
+            self.fd_dict.remove(&child).unwrap_or_default()
+        };
+        if share {
+            self.fd_group_dict.insert(child, owner);
+            self.fd_dict.entry(owner).or_default().extend(own);
+        } else {
+            self.fd_group_dict.remove(&child);
+            let mut table = self.fd_dict.get(&owner).cloned().unwrap_or_default();
+            table.extend(own);
+            self.fd_dict.insert(child, table);
+        }
+    }
+
+    /// Stop sharing an fd table (`unshare(CLONE_FILES)`, `close_range` with
+    /// `CLOSE_RANGE_UNSHARE`).
+    pub fn unshare_fds(&mut self, pid: i32) {
+        let owner = self.fd_owner(pid);
+        if owner != pid {
+            let table = self.fd_dict.get(&owner).cloned().unwrap_or_default();
+            self.fd_group_dict.remove(&pid);
+            self.fd_dict.insert(pid, table);
+        }
+    }
+
+    pub fn push_half_line(& mut self, pid: i32, l: & str){
+        let s = match l.find("<unfinished") {
+            Some(pos) => &l[0..pos],
+            None => ""
+        };
+        let s = s.trim().to_string();
+        self.line_dict.insert(pid, s);
+
+    }
+
+    pub fn pop_complete_line(&mut self, pid: i32, l: &str) -> Result<String, ParseErrorKind> {
+        let index = match l.find("resumed>") {
+            Some(pos) => pos + "resumed>".len(),
+            None => return Err(ParseErrorKind::UnmatchedResume)
+        };
+        let line = self.line_dict.remove(&pid).ok_or(ParseErrorKind::UnmatchedResume)?;
+        Ok(line + &l[index..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::tests::accesses;
+
+    #[test]
+    fn fds_resolve_without_annotations() {
+        let trace = r#"
+            1 openat(AT_FDCWD, "in", O_RDONLY) = 3
+            1 dup2(3, 7) = 7
+            1 close(3) = 0
+            1 read(7, "x", 1) = 1
+            1 fcntl(7, F_DUPFD_CLOEXEC, 10) = 10
+            1 read(10, "y", 1) = 1
+            1 openat(AT_FDCWD, "out", O_WRONLY|O_CREAT|O_TRUNC, 0644) = 3
+            1 write(3, "z", 1) = 1
+        "#;
+        assert_eq!(accesses(trace), ["r /w/in", "r /w/in", "r /w/in", "w /w/out", "w /w/out"]);
+    }
+
+    #[test]
+    fn clone_files_shares_the_table_until_unshared() {
+        let trace = r#"
+            1 clone(child_stack=NULL, flags=CLONE_FILES|SIGCHLD) = 2
+            2 openat(AT_FDCWD, "shared", O_RDONLY) = 3
+            1 read(3, "x", 1) = 1
+            1 clone(child_stack=NULL, flags=SIGCHLD) = 4
+            4 close(3) = 0
+            4 openat(AT_FDCWD, "own", O_RDONLY) = 3
+            1 read(3, "x", 1) = 1
+            4 read(3, "x", 1) = 1
+            2 unshare(CLONE_FILES) = 0
+            2 close(3) = 0
+            2 openat(AT_FDCWD, "mine", O_RDONLY) = 3
+            1 read(3, "x", 1) = 1
+            2 read(3, "x", 1) = 1
+        "#;
+        let expected = [
+            "r /w/shared", "r /w/shared",
+            "r /w/own", "r /w/shared", "r /w/own",
+            "r /w/mine", "r /w/shared", "r /w/mine",
+        ];
+        assert_eq!(accesses(trace), expected);
+    }
+
+    fn error(line: usize) -> ParseError {
+        ParseError { kind: ParseErrorKind::MissingPid, line, pid: None, syscall: None, text: String::new() }
+    }
+
+    #[test]
+    fn lenient_diagnostics_are_capped() {
+        let mut ctx = Context::new();
+        for line in 0..MAX_DIAGNOSTICS + 5 {
+            assert!(ctx.report(error(line)).is_ok());
+        }
+        assert_eq!(ctx.diagnostics().len(), MAX_DIAGNOSTICS);
+        assert_eq!(ctx.diagnostics().last().map(|e| e.line), Some(MAX_DIAGNOSTICS - 1));
+        assert_eq!(ctx.dropped_diagnostics(), 5);
+    }
+}
@@ -0,0 +1,162 @@
+use std::fmt;
+
+/// What went wrong while parsing a trace line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    /// The line does not start with a pid (trace not recorded with `-f`).
+    MissingPid,
+    BadPid(String),
+    /// A `<... resumed>` line with no matching `<unfinished ...>` call.
+    UnmatchedResume,
+    /// Expected a quoted string argument.
+    BadString(String),
+    MissingArgument(usize),
+    /// An fd with no `-y` `<path>` annotation that the fd table does not know.
+    MissingFdPath(String),
+    MissingCloneFlags,
+    BadExitStatus(String),
+    UnhandledInfo,
+    UnclassifiedSyscall,
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::MissingPid => write!(f, "expected a leading pid"),
+            ParseErrorKind::BadPid(s) => write!(f, "invalid pid `{s}`"),
+            ParseErrorKind::UnmatchedResume => write!(f, "resumed call has no unfinished half"),
+            ParseErrorKind::BadString(s) => write!(f, "expected a quoted string, found `{s}`"),
+            ParseErrorKind::MissingArgument(i) => write!(f, "missing argument {i}"),
+            ParseErrorKind::MissingFdPath(s) => write!(f, "unknown fd `{s}` (no <path> annotation or earlier open)"),
+            ParseErrorKind::MissingCloneFlags => write!(f, "clone call without flags="),
+            ParseErrorKind::BadExitStatus(s) => write!(f, "invalid exit status `{s}`"),
+            ParseErrorKind::UnhandledInfo => write!(f, "unrecognised +++ line"),
+            ParseErrorKind::UnclassifiedSyscall => write!(f, "unclassified syscall"),
+        }
+    }
+}
+
+/// A [`ParseErrorKind`] together with where in the trace it happened.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// 1-based line number in the trace.
+    pub line: usize,
+    pub pid: Option<i32>,
+    pub syscall: Option<String>,
+    /// The raw trace line.
+    pub text: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}", self.line)?;
+        if let Some(pid) = self.pid {
+            write!(f, ", pid {pid}")?;
+        }
+        if let Some(syscall) = &self.syscall {
+            write!(f, ", {syscall}")?;
+        }
+        write!(f, ": {}: {}", self.kind, self.text)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// How [`crate::parse_and_gather_cmd_rw_sets`] reacts to a bad line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorMode {
+    /// Record the error in [`crate::Context::diagnostics`] (or just count it,
+    /// past [`crate::MAX_DIAGNOSTICS`]) and keep going.
+    #[default]
+    Lenient,
+    /// Stop at the first error.
+    Strict,
+}
+
+/// Failure while reading a trace from a stream.
+#[derive(Debug)]
+pub enum StreamError {
+    Io(std::io::Error),
+    Parse(ParseError),
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamError::Io(e) => write!(f, "{e}"),
+            StreamError::Parse(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StreamError::Io(e) => Some(e),
+            StreamError::Parse(e) => Some(e),
+        }
+    }
+}
+
+impl From<std::io::Error> for StreamError {
+    fn from(e: std::io::Error) -> Self {
+        StreamError::Io(e)
+    }
+}
+
+impl From<ParseError> for StreamError {
+    fn from(e: ParseError) -> Self {
+        StreamError::Parse(e)
+    }
+}
+
+/// Failure while loading a [`crate::table::SyscallTable`].
+#[derive(Debug)]
+pub enum TableError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Toml(toml::de::Error),
+    /// Well-formed, but a rule does not make sense for its layout.
+    Invalid(String),
+}
+
+impl fmt::Display for TableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TableError::Io(e) => write!(f, "{e}"),
+            TableError::Json(e) => write!(f, "{e}"),
+            TableError::Toml(e) => write!(f, "{e}"),
+            TableError::Invalid(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+impl std::error::Error for TableError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TableError::Io(e) => Some(e),
+            TableError::Json(e) => Some(e),
+            TableError::Toml(e) => Some(e),
+            TableError::Invalid(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for TableError {
+    fn from(e: std::io::Error) -> Self {
+        TableError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for TableError {
+    fn from(e: serde_json::Error) -> Self {
+        TableError::Json(e)
+    }
+}
+
+impl From<toml::de::Error> for TableError {
+    fn from(e: toml::de::Error) -> Self {
+        TableError::Toml(e)
+    }
+}
@@ -0,0 +1,87 @@
+use serde::Serialize;
+
+use crate::context::Context;
+use crate::error::ParseError;
+use crate::files::{RwFile, RFile, WFile};
+use crate::flags::OpenIntent;
+use crate::parse::{parse_record, Record, SyscallLine};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Access {
+    Read,
+    Write,
+}
+
+impl std::str::FromStr for Access {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" | "r" => Ok(Access::Read),
+            "write" | "w" => Ok(Access::Write),
+            _ => Err(format!("unknown access `{s}` (expected read or write)")),
+        }
+    }
+}
+
+/// One file access together with the call it came from.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AccessEvent {
+    /// 1-based trace line the call completed on.
+    pub line: usize,
+    pub pid: i32,
+    pub syscall: String,
+    pub path: String,
+    pub access: Access,
+    /// For opens, what the flags asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub intent: Option<OpenIntent>,
+    /// For data calls, how many bytes were read or written.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+    /// Return value as strace printed it, e.g. `3</etc/passwd>` or
+    /// `-1 ENOENT (No such file or directory)`.
+    pub ret: String,
+}
+
+impl AccessEvent {
+    pub fn to_rwfile(&self) -> RwFile {
+        match self.access {
+            Access::Read => RwFile::Read(RFile { intent: self.intent, bytes: self.bytes, ..RFile::new(&self.path) }),
+            Access::Write => RwFile::Write(WFile { intent: self.intent, bytes: self.bytes, ..WFile::new(&self.path) }),
+        }
+    }
+
+    /// Events for the files a classified call touched.
+    pub fn from_call(call: &SyscallLine, files: Vec<RwFile>) -> Vec<AccessEvent> {
+        files
+            .into_iter()
+            .map(|f| {
+                let (path, access, intent, bytes) = match f {
+                    RwFile::Read(r) => (r.fname, Access::Read, r.intent, r.bytes),
+                    RwFile::Write(w) => (w.fname, Access::Write, w.intent, w.bytes),
+                };
+                AccessEvent {
+                    line: call.line,
+                    pid: call.pid,
+                    syscall: call.syscall.clone(),
+                    path,
+                    access,
+                    intent,
+                    bytes,
+                    ret: call.ret.trim().to_string(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Like [`crate::parse_line`], but keeps the pid, syscall, return value and
+/// line number of each access.
+pub fn parse_line_events(l: &str, ctx: &mut Context) -> Result<Vec<AccessEvent>, ParseError> {
+    match parse_record(l, ctx)? {
+        Record::Call { call, files: Some(files) } => Ok(AccessEvent::from_call(&call, files)),
+        _ => Ok(vec![]),
+    }
+}
@@ -0,0 +1,392 @@
+//! Which paths are kept out of the read and write sets.
+//!
+//! Rules are tried in order and the first one that matches a path decides;
+//! a path no rule matches is kept. A rule matches a path if its pattern
+//! matches the path itself or one of its parent directories, so `/dev`
+//! covers `/dev/null`. Patterns with `*`, `?` or `**` are globs (`*` and
+//! `?` stop at `/`, `**` does not); anything else is a path prefix compared
+//! a component at a time.
+//!
+//! A rules file lists rules in the same order, in TOML:
+//!
+//! ```toml
+//! [[rule]]
+//! include = "/dev/shm"
+//!
+//! [[rule]]
+//! exclude = "/var/cache/**/*.tmp"
+//! sets = "write"
+//! ```
+//!
+//! or as JSON, `{"rule": [{"include": "/dev/shm"}, ...]}`.
+
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::TableError;
+use crate::event::Access;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    Include,
+    Exclude,
+}
+
+/// Which set a rule applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleSets {
+    #[default]
+    Both,
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pattern {
+    Prefix(String),
+    Glob(String),
+}
+
+/// One element of a glob.
+#[derive(Debug, Clone, Copy)]
+enum Token {
+    Byte(u8),
+    /// `?`
+    One,
+    /// `*`
+    Star,
+    /// `**/`: nothing, or anything up to and including a `/`.
+    Dirs,
+    /// `**` not followed by `/`.
+    Any,
+}
+
+fn tokens(p: &[u8]) -> Vec<Token> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < p.len() {
+        let (tok, len) = match &p[i..] {
+            [b'*', b'*', b'/', ..] => (Token::Dirs, 3),
+            [b'*', b'*', ..] => (Token::Any, 2),
+            [b'*', ..] => (Token::Star, 1),
+            [b'?', ..] => (Token::One, 1),
+            [c, ..] => (Token::Byte(*c), 1),
+            [] => unreachable!(),
+        };
+        out.push(tok);
+        i += len;
+    }
+    out
+}
+
+/// `*` and `?` within one component, `**` across any number of them.
+///
+/// Works back from the last token, keeping the offsets of `s` from which
+/// the rest of the pattern matches, so the cost stays at pattern length
+/// times path length however many stars there are.
+fn glob_match(p: &[u8], s: &[u8]) -> bool {
+    let m = s.len();
+    let mut next: Vec<bool> = (0..=m).map(|j| j == m).collect();
+    for tok in tokens(p).into_iter().rev() {
+        let mut cur = vec![false; m + 1];
+        let mut dirs = false;
+        for j in (0..=m).rev() {
+            let c = s.get(j).copied();
+            cur[j] = match tok {
+                Token::Byte(b) => c == Some(b) && next[j + 1],
+                Token::One => c.is_some_and(|c| c != b'/') && next[j + 1],
+                Token::Star => next[j] || (c.is_some_and(|c| c != b'/') && cur[j + 1]),
+                Token::Any => next[j] || (c.is_some() && cur[j + 1]),
+                Token::Dirs => {
+                    dirs |= c == Some(b'/') && next[j + 1];
+                    next[j] || dirs
+                }
+            };
+        }
+        next = cur;
+    }
+    next[0]
+}
+
+impl Pattern {
+    /// A trailing `/` is dropped: the pattern already covers what is
+    /// below the directory.
+    pub fn parse(s: &str) -> Pattern {
+        if s.contains(['*', '?']) {
+            Pattern::Glob(s.trim_end_matches('/').to_string())
+        } else {
+            Pattern::Prefix(s.trim_end_matches('/').to_string())
+        }
+    }
+
+    /// The pattern matches `path` or one of its parents.
+    pub fn matches(&self, path: &str) -> bool {
+        match self {
+            Pattern::Prefix(prefix) => path
+                .strip_prefix(prefix.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/') || prefix.is_empty()),
+            Pattern::Glob(glob) => {
+                glob_match(glob.as_bytes(), path.as_bytes())
+                    || path
+                        .match_indices('/')
+                        .any(|(i, _)| i > 0 && glob_match(glob.as_bytes(), &path.as_bytes()[..i]))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Prefix(p) if p.is_empty() => f.write_str("/"),
+            Pattern::Prefix(p) | Pattern::Glob(p) => f.write_str(p),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathRule {
+    pub action: RuleAction,
+    pub pattern: Pattern,
+    pub sets: RuleSets,
+}
+
+impl PathRule {
+    pub fn include(pattern: &str) -> PathRule {
+        PathRule { action: RuleAction::Include, pattern: Pattern::parse(pattern), sets: RuleSets::Both }
+    }
+
+    pub fn exclude(pattern: &str) -> PathRule {
+        PathRule { action: RuleAction::Exclude, pattern: Pattern::parse(pattern), sets: RuleSets::Both }
+    }
+
+    /// Parse the CLI form `[read:|write:]pattern`.
+    pub fn parse(action: RuleAction, spec: &str) -> PathRule {
+        let (sets, pattern) = match spec.split_once(':') {
+            Some(("read", p)) => (RuleSets::Read, p),
+            Some(("write", p)) => (RuleSets::Write, p),
+            _ => (RuleSets::Both, spec),
+        };
+        PathRule { action, pattern: Pattern::parse(pattern), sets }
+    }
+
+    pub fn only(mut self, sets: RuleSets) -> PathRule {
+        self.sets = sets;
+        self
+    }
+
+    fn applies(&self, path: &str, access: Access) -> bool {
+        let set_ok = matches!(
+            (self.sets, access),
+            (RuleSets::Both, _) | (RuleSets::Read, Access::Read) | (RuleSets::Write, Access::Write)
+        );
+        set_ok && self.pattern.matches(path)
+    }
+}
+
+/// The CLI form, e.g. `exclude=/dev` or `include=write:/dev/shm`.
+impl fmt::Display for PathRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self.action {
+            RuleAction::Include => "include",
+            RuleAction::Exclude => "exclude",
+        };
+        let sets = match self.sets {
+            RuleSets::Both => "",
+            RuleSets::Read => "read:",
+            RuleSets::Write => "write:",
+        };
+        write!(f, "{action}={sets}{}", self.pattern)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleEntry {
+    include: Option<String>,
+    exclude: Option<String>,
+    #[serde(default)]
+    sets: RuleSets,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    rule: Vec<RuleEntry>,
+}
+
+/// How many accesses an exclude rule kept out of the sets.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Suppressed {
+    pub rule: String,
+    pub count: usize,
+}
+
+/// An ordered list of [`PathRule`]s, counting what each one suppresses.
+/// The default is [`PathRules::defaults`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathRules {
+    rules: Vec<PathRule>,
+    suppressed: Vec<usize>,
+}
+
+impl Default for PathRules {
+    fn default() -> Self {
+        PathRules::defaults()
+    }
+}
+
+impl PathRules {
+    /// No rules: every path is kept.
+    pub fn none() -> PathRules {
+        PathRules { rules: Vec::new(), suppressed: Vec::new() }
+    }
+
+    /// Pseudo-filesystems and devices, and PaSh's speculative scratch files.
+    pub fn defaults() -> PathRules {
+        let mut rules = PathRules::none();
+        for p in ["/proc", "/sys", "/dev", "/tmp/pash_spec*"] {
+            rules.push(PathRule::exclude(p));
+        }
+        rules
+    }
+
+    fn from_file(file: RulesFile) -> Result<PathRules, TableError> {
+        let mut rules = PathRules::none();
+        for entry in file.rule {
+            let rule = match (entry.include, entry.exclude) {
+                (Some(p), None) => PathRule::include(&p),
+                (None, Some(p)) => PathRule::exclude(&p),
+                _ => return Err(TableError::Invalid("each rule needs exactly one of include or exclude".to_string())),
+            };
+            rules.push(rule.only(entry.sets));
+        }
+        Ok(rules)
+    }
+
+    pub fn from_json(s: &str) -> Result<PathRules, TableError> {
+        PathRules::from_file(serde_json::from_str(s)?)
+    }
+
+    pub fn from_toml(s: &str) -> Result<PathRules, TableError> {
+        PathRules::from_file(toml::from_str(s)?)
+    }
+
+    /// Read a rules file: JSON if it ends in `.json`, TOML otherwise.
+    pub fn load(path: &Path) -> Result<PathRules, TableError> {
+        let s = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => PathRules::from_json(&s),
+            _ => PathRules::from_toml(&s),
+        }
+    }
+
+    /// Add a rule after the existing ones, so it only sees paths none of
+    /// them matched.
+    pub fn push(&mut self, rule: PathRule) {
+        self.rules.push(rule);
+        self.suppressed.push(0);
+    }
+
+    pub fn extend(&mut self, other: PathRules) {
+        for rule in other.rules {
+            self.push(rule);
+        }
+    }
+
+    pub fn rules(&self) -> &[PathRule] {
+        &self.rules
+    }
+
+    /// Whether an access to `path` goes into its set, counting it against
+    /// the rule that excluded it if not.
+    pub fn allows(&mut self, path: &str, access: Access) -> bool {
+        match self.rules.iter().position(|r| r.applies(path, access)) {
+            Some(i) if self.rules[i].action == RuleAction::Exclude => {
+                self.suppressed[i] += 1;
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Every exclude rule with the number of accesses it suppressed.
+    pub fn suppressed(&self) -> Vec<Suppressed> {
+        self.rules
+            .iter()
+            .zip(&self.suppressed)
+            .filter(|(r, _)| r.action == RuleAction::Exclude)
+            .map(|(r, &count)| Suppressed { rule: r.to_string(), count })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob(p: &str, s: &str) -> bool {
+        Pattern::parse(p).matches(s)
+    }
+
+    #[test]
+    fn star_stays_within_a_component() {
+        assert!(glob("/tmp/*.log", "/tmp/a.log"));
+        assert!(glob("/tmp/*.log", "/tmp/.log"));
+        assert!(!glob("/tmp/*.log", "/tmp/a/b.log"));
+        assert!(glob("/tmp/pash_spec*", "/tmp/pash_spec12/out"));
+    }
+
+    #[test]
+    fn double_star_crosses_components() {
+        assert!(glob("/var/cache/**/*.tmp", "/var/cache/x.tmp"));
+        assert!(glob("/var/cache/**/*.tmp", "/var/cache/a/b/x.tmp"));
+        assert!(!glob("/var/cache/**/*.tmp", "/var/cachex.tmp"));
+        assert!(glob("/home/**", "/home/u/.cache/f"));
+        assert!(glob("/a**z", "/a/b/z"));
+    }
+
+    #[test]
+    fn question_mark_is_one_byte_but_not_a_slash() {
+        assert!(glob("/dev/tty?", "/dev/tty1"));
+        assert!(!glob("/dev/tty?", "/dev/tty"));
+        assert!(!glob("/a?b", "/a/b"));
+    }
+
+    #[test]
+    fn trailing_slash_covers_the_directory() {
+        assert!(glob("/dev/", "/dev"));
+        assert!(glob("/dev/", "/dev/null"));
+        assert!(!glob("/dev/", "/devices"));
+        assert!(glob("/tmp/*/", "/tmp/x"));
+        assert!(glob("/tmp/*/", "/tmp/x/f"));
+    }
+
+    #[test]
+    fn many_stars_do_not_backtrack_forever() {
+        let path = format!("/{}", "a".repeat(4000));
+        assert!(!glob("/*a*a*a*a*a*a*a*a*a*a*a*a*b", &path));
+        assert!(!glob("/**a**a**a**a**a**a**a**a**b", &path));
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let mut rules = PathRules::none();
+        rules.push(PathRule::include("/dev/shm"));
+        rules.push(PathRule::exclude("/dev"));
+        rules.push(PathRule::exclude("/data").only(RuleSets::Write));
+        assert!(rules.allows("/dev/shm/x", Access::Write));
+        assert!(!rules.allows("/dev/null", Access::Read));
+        assert!(rules.allows("/data/in", Access::Read));
+        assert!(!rules.allows("/data/out", Access::Write));
+        assert!(rules.allows("/etc/passwd", Access::Read));
+
+        let counts: Vec<_> = rules.suppressed().into_iter().map(|s| (s.rule, s.count)).collect();
+        assert_eq!(counts, [("exclude=/dev".to_string(), 1), ("exclude=write:/data".to_string(), 1)]);
+    }
+}
@@ -0,0 +1,150 @@
+use std::path::Path;
+
+use crate::flags::OpenIntent;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RFile {
+    pub fname: String,
+    /// Set for files reached through an open.
+    pub intent: Option<OpenIntent>,
+    /// Bytes moved by a data call (`read`, `write`, `sendfile`, ...).
+    pub bytes: Option<u64>,
+}
+
+impl RFile {
+    pub fn new(path: &str) -> Self {
+        RFile {
+            fname: path.to_string(),
+            intent: None,
+            bytes: None,
+        }
+    }
+
+    pub fn with_intent(mut self, intent: OpenIntent) -> Self {
+        self.intent = Some(intent);
+        self
+    }
+
+    pub fn with_bytes(mut self, bytes: u64) -> Self {
+        self.bytes = Some(bytes);
+        self
+    }
+
+    pub fn closure(&self) -> Vec<RFile> {
+
+        let mut all_files = vec![self.clone()];
+
+        if !self.fname.starts_with('/') {
+            return all_files;
+        }
+
+        // Every parent is shorter than its child, so this always reaches `/`.
+        let mut current_path = Path::new(&self.fname);
+
+        while let Some(parent) = current_path.parent() {
+            if parent == Path::new("") || parent == Path::new("/") {
+                break;
+            }
+
+            all_files.push(RFile {
+                fname: parent.display().to_string(),
+                intent: None,
+                bytes: None,
+            });
+
+            current_path = parent;
+        }
+
+        all_files
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WFile {
+    pub fname: String,
+    /// Set for files reached through an open.
+    pub intent: Option<OpenIntent>,
+    /// Bytes moved by a data call (`read`, `write`, `sendfile`, ...).
+    pub bytes: Option<u64>,
+}
+
+impl WFile {
+    pub fn new(path: &str) -> Self {
+        // let path_buf = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+        //might need to revert to this later
+        WFile {
+            // fname: path_buf.to_str().unwrap().to_string(),
+            fname: path.to_string(),
+            intent: None,
+            bytes: None,
+        }
+    }
+
+    pub fn with_intent(mut self, intent: OpenIntent) -> Self {
+        self.intent = Some(intent);
+        self
+    }
+
+    pub fn with_bytes(mut self, bytes: u64) -> Self {
+        self.bytes = Some(bytes);
+        self
+    }
+
+    pub fn closure(&self) -> Vec<WFile> {
+
+        let mut all_files = vec![self.clone()];
+
+        if !self.fname.starts_with('/') {
+            return all_files;
+        }
+
+        // Every parent is shorter than its child, so this always reaches `/`.
+        let mut current_path = Path::new(&self.fname);
+
+        while let Some(parent) = current_path.parent() {
+            if parent == Path::new("") || parent == Path::new("/") {
+                break;
+            }
+
+            all_files.push(WFile {
+                fname: parent.display().to_string(),
+                intent: None,
+                bytes: None,
+            });
+
+            current_path = parent;
+        }
+
+        all_files
+    }
+}
+
+/// A single file access recorded from a trace line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RwFile {
+    Read(RFile),
+    Write(WFile)
+}
+
+impl RwFile {
+    pub fn fname(&self) -> &str {
+        match self {
+            RwFile::Read(r) => &r.fname,
+            RwFile::Write(w) => &w.fname,
+        }
+    }
+
+    pub fn intent(&self) -> Option<OpenIntent> {
+        match self {
+            RwFile::Read(r) => r.intent,
+            RwFile::Write(w) => w.intent,
+        }
+    }
+
+    pub fn bytes(&self) -> Option<u64> {
+        match self {
+            RwFile::Read(r) => r.bytes,
+            RwFile::Write(w) => w.bytes,
+        }
+    }
+}
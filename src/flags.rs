@@ -0,0 +1,365 @@
+//! Typed flag arguments, decoded from strace's `O_WRONLY|O_CREAT|0x80000`
+//! rendering.
+//!
+//! Values are those of Linux on x86_64, which most architectures share.
+//! A few differ elsewhere (on aarch64, `O_DIRECTORY`, `O_NOFOLLOW`,
+//! `O_DIRECT` and `O_LARGEFILE` are swapped around), so decoding goes by
+//! the names strace prints, and the tracer decodes registers with the
+//! host's own values before handing names over. Names strace prints that
+//! are not in a table (newer kernels, exit signals in clone flags) are
+//! ignored; bare numbers are taken as raw x86_64 bits.
+
+use std::ops::BitOr;
+use std::sync::OnceLock;
+
+use serde::ser::{Serialize, SerializeSeq, Serializer};
+
+use crate::args::Arg;
+
+fn parse_bits(s: &str) -> Option<u64> {
+    if let Some(hex) = s.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16).ok()
+    } else if s.len() > 1 && s.starts_with('0') {
+        u64::from_str_radix(&s[1..], 8).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+/// `bits` as one hex number per set bit (`0x80000`), or `0`, so each part
+/// of an [`Arg::Flags`] built from them can be a `&'static str`.
+pub(crate) fn bit_parts(bits: u64) -> Vec<&'static str> {
+    static PARTS: OnceLock<Vec<String>> = OnceLock::new();
+    let parts = PARTS.get_or_init(|| (0..64).map(|b| format!("{:#x}", 1u64 << b)).collect());
+    if bits == 0 {
+        return vec!["0"];
+    }
+    (0..64).filter(|b| bits & 1 << b != 0).map(|b| parts[b].as_str()).collect()
+}
+
+macro_rules! flag_set {
+    ($(#[$meta:meta])* $name:ident { $($flag:ident = $value:expr,)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+        pub struct $name(pub u64);
+
+        impl $name {
+            $(pub const $flag: $name = $name($value);)*
+
+            const NAMES: &'static [(&'static str, u64)] = &[$((stringify!($flag), $value),)*];
+
+            pub fn empty() -> Self {
+                $name(0)
+            }
+
+            pub fn bits(self) -> u64 {
+                self.0
+            }
+
+            /// All bits of `other` are set.
+            pub fn contains(self, other: Self) -> bool {
+                self.0 & other.0 == other.0
+            }
+
+            pub fn from_name(name: &str) -> Option<Self> {
+                Self::NAMES.iter().find(|(n, _)| *n == name).map(|&(_, v)| $name(v))
+            }
+
+            /// OR together names and numbers; unknown names are skipped.
+            pub fn from_parts(parts: &[&str]) -> Self {
+                let bits = parts
+                    .iter()
+                    .filter_map(|p| Self::from_name(p).map(|f| f.0).or_else(|| parse_bits(p)))
+                    .fold(0, |acc, b| acc | b);
+                $name(bits)
+            }
+
+            /// The known names set in `self`, and the bits none of them cover.
+            fn names(self) -> (Vec<&'static str>, u64) {
+                if self.0 == 0
+                    && let Some(&(name, _)) = Self::NAMES.iter().find(|(_, v)| *v == 0)
+                {
+                    return (vec![name], 0);
+                }
+                let mut names = Vec::new();
+                let mut rest = self.0;
+                for &(name, bit) in Self::NAMES {
+                    if bit != 0 && self.0 & bit == bit && rest & bit != 0 {
+                        names.push(name);
+                        rest &= !bit;
+                    }
+                }
+                (names, rest)
+            }
+
+            /// Render the way strace does: known names, then any leftover bits.
+            pub fn render(self) -> String {
+                let (names, rest) = self.names();
+                let mut parts: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+                if rest != 0 || parts.is_empty() {
+                    parts.push(format!("{rest:#x}"));
+                }
+                parts.join("|")
+            }
+
+            /// The parts of an [`Arg::Flags`] for `self`: known names, then
+            /// the leftover bits one at a time.
+            pub fn parts(self) -> Vec<&'static str> {
+                let (mut parts, rest) = self.names();
+                if rest != 0 || parts.is_empty() {
+                    parts.extend(bit_parts(rest));
+                }
+                parts
+            }
+
+            /// Decode a flag argument (`A|B`, `flags=A|B` or a bare number).
+            pub fn from_arg(arg: &Arg) -> Option<Self> {
+                match arg {
+                    Arg::Flags(parts) => Some(Self::from_parts(parts)),
+                    Arg::Int(i) => Some($name(*i as u64)),
+                    Arg::Named(_, a) | Arg::Commented(a, _) => Self::from_arg(a),
+                    _ => None,
+                }
+            }
+        }
+
+        impl BitOr for $name {
+            type Output = $name;
+
+            fn bitor(self, rhs: $name) -> $name {
+                $name(self.0 | rhs.0)
+            }
+        }
+    };
+}
+
+flag_set! {
+    /// `open`/`openat`/`creat` flags. `O_RDONLY` is zero, so test the access
+    /// mode with [`OpenFlags::access_mode`] rather than `contains`.
+    OpenFlags {
+        O_RDONLY = 0o0,
+        O_WRONLY = 0o1,
+        O_RDWR = 0o2,
+        O_CREAT = 0o100,
+        O_EXCL = 0o200,
+        O_NOCTTY = 0o400,
+        O_TRUNC = 0o1000,
+        O_APPEND = 0o2000,
+        O_NONBLOCK = 0o4000,
+        O_DSYNC = 0o10000,
+        O_ASYNC = 0o20000,
+        O_DIRECT = 0o40000,
+        O_LARGEFILE = 0o100000,
+        O_DIRECTORY = 0o200000,
+        O_NOFOLLOW = 0o400000,
+        O_NOATIME = 0o1000000,
+        O_CLOEXEC = 0o2000000,
+        O_SYNC = 0o4010000,
+        O_PATH = 0o10000000,
+        O_TMPFILE = 0o20200000,
+    }
+}
+
+flag_set! {
+    /// `AT_*` flags of the `*at` family.
+    AtFlags {
+        AT_SYMLINK_NOFOLLOW = 0x100,
+        AT_REMOVEDIR = 0x200,
+        AT_EACCESS = 0x200,
+        AT_SYMLINK_FOLLOW = 0x400,
+        AT_NO_AUTOMOUNT = 0x800,
+        AT_EMPTY_PATH = 0x1000,
+        AT_STATX_FORCE_SYNC = 0x2000,
+        AT_STATX_DONT_SYNC = 0x4000,
+        AT_RECURSIVE = 0x8000,
+    }
+}
+
+flag_set! {
+    /// `clone`/`clone3` flags. The exit signal strace prints alongside them
+    /// (`SIGCHLD`) is not a flag and is dropped.
+    CloneFlags {
+        CLONE_NEWTIME = 0x80,
+        CLONE_VM = 0x100,
+        CLONE_FS = 0x200,
+        CLONE_FILES = 0x400,
+        CLONE_SIGHAND = 0x800,
+        CLONE_PIDFD = 0x1000,
+        CLONE_PTRACE = 0x2000,
+        CLONE_VFORK = 0x4000,
+        CLONE_PARENT = 0x8000,
+        CLONE_THREAD = 0x10000,
+        CLONE_NEWNS = 0x20000,
+        CLONE_SYSVSEM = 0x40000,
+        CLONE_SETTLS = 0x80000,
+        CLONE_PARENT_SETTID = 0x100000,
+        CLONE_CHILD_CLEARTID = 0x200000,
+        CLONE_DETACHED = 0x400000,
+        CLONE_UNTRACED = 0x800000,
+        CLONE_CHILD_SETTID = 0x1000000,
+        CLONE_NEWCGROUP = 0x2000000,
+        CLONE_NEWUTS = 0x4000000,
+        CLONE_NEWIPC = 0x8000000,
+        CLONE_NEWUSER = 0x10000000,
+        CLONE_NEWPID = 0x20000000,
+        CLONE_NEWNET = 0x40000000,
+        CLONE_IO = 0x80000000,
+        CLONE_CLEAR_SIGHAND = 0x100000000,
+        CLONE_INTO_CGROUP = 0x200000000,
+    }
+}
+
+flag_set! {
+    /// `renameat2` flags.
+    RenameFlags {
+        RENAME_NOREPLACE = 0x1,
+        RENAME_EXCHANGE = 0x2,
+        RENAME_WHITEOUT = 0x4,
+    }
+}
+
+flag_set! {
+    /// `resolve` field of `openat2`'s `struct open_how`.
+    ResolveFlags {
+        RESOLVE_NO_XDEV = 0x1,
+        RESOLVE_NO_MAGICLINKS = 0x2,
+        RESOLVE_NO_SYMLINKS = 0x4,
+        RESOLVE_BENEATH = 0x8,
+        RESOLVE_IN_ROOT = 0x10,
+        RESOLVE_CACHED = 0x20,
+    }
+}
+
+flag_set! {
+    /// `mmap`/`mprotect` protection.
+    ProtFlags {
+        PROT_NONE = 0x0,
+        PROT_READ = 0x1,
+        PROT_WRITE = 0x2,
+        PROT_EXEC = 0x4,
+        PROT_SEM = 0x8,
+        PROT_GROWSDOWN = 0x1000000,
+        PROT_GROWSUP = 0x2000000,
+    }
+}
+
+flag_set! {
+    /// `mmap` flags. The low two bits are the mapping type, so use
+    /// [`MapFlags::is_shared`] rather than `contains(MAP_SHARED)`.
+    MapFlags {
+        MAP_SHARED_VALIDATE = 0x3,
+        MAP_SHARED = 0x1,
+        MAP_PRIVATE = 0x2,
+        MAP_FIXED = 0x10,
+        MAP_ANONYMOUS = 0x20,
+        MAP_GROWSDOWN = 0x100,
+        MAP_DENYWRITE = 0x800,
+        MAP_EXECUTABLE = 0x1000,
+        MAP_LOCKED = 0x2000,
+        MAP_NORESERVE = 0x4000,
+        MAP_POPULATE = 0x8000,
+        MAP_NONBLOCK = 0x10000,
+        MAP_STACK = 0x20000,
+        MAP_HUGETLB = 0x40000,
+        MAP_SYNC = 0x80000,
+        MAP_FIXED_NOREPLACE = 0x100000,
+    }
+}
+
+impl MapFlags {
+    pub const MAP_TYPE: u64 = 0x3;
+
+    /// `MAP_SHARED` or `MAP_SHARED_VALIDATE`: stores reach the file.
+    pub fn is_shared(self) -> bool {
+        matches!(self.0 & Self::MAP_TYPE, 0x1 | 0x3)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessMode {
+    ReadOnly,
+    WriteOnly,
+    ReadWrite,
+}
+
+impl OpenFlags {
+    pub const O_ACCMODE: u64 = 0o3;
+
+    pub fn access_mode(self) -> AccessMode {
+        match self.0 & Self::O_ACCMODE {
+            0 => AccessMode::ReadOnly,
+            1 => AccessMode::WriteOnly,
+            _ => AccessMode::ReadWrite,
+        }
+    }
+
+    pub fn intent(self) -> OpenIntent {
+        let mode = self.access_mode();
+        OpenIntent {
+            read: mode != AccessMode::WriteOnly,
+            write: mode != AccessMode::ReadOnly,
+            create: self.contains(Self::O_CREAT) || self.contains(Self::O_TMPFILE),
+            excl: self.contains(Self::O_EXCL),
+            trunc: self.contains(Self::O_TRUNC),
+            append: self.contains(Self::O_APPEND),
+            directory: self.contains(Self::O_DIRECTORY),
+            path: self.contains(Self::O_PATH),
+        }
+    }
+}
+
+/// What an open asks for, decoded from its flags. Serializes as the list
+/// of the names of the set fields, e.g. `["write","create","trunc"]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct OpenIntent {
+    pub read: bool,
+    pub write: bool,
+    pub create: bool,
+    /// `O_EXCL`: creation must not find an existing file.
+    pub excl: bool,
+    pub trunc: bool,
+    pub append: bool,
+    pub directory: bool,
+    /// `O_PATH`: only a handle to the path, the contents are not accessed.
+    pub path: bool,
+}
+
+impl OpenIntent {
+    /// The contents may change: written, created or truncated. `O_PATH`
+    /// ignores the other flags.
+    pub fn modifies(self) -> bool {
+        !self.path && (self.write || self.create || self.trunc)
+    }
+
+    /// The contents may be read. `O_PATH` opens do not count.
+    pub fn reads(self) -> bool {
+        !self.path && self.read
+    }
+
+    pub fn names(self) -> Vec<&'static str> {
+        [
+            (self.read, "read"),
+            (self.write, "write"),
+            (self.create, "create"),
+            (self.excl, "excl"),
+            (self.trunc, "trunc"),
+            (self.append, "append"),
+            (self.directory, "directory"),
+            (self.path, "path"),
+        ]
+        .into_iter()
+        .filter_map(|(set, name)| set.then_some(name))
+        .collect()
+    }
+}
+
+impl Serialize for OpenIntent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let names = self.names();
+        let mut seq = serializer.serialize_seq(Some(names.len()))?;
+        for name in names {
+            seq.serialize_element(name)?;
+        }
+        seq.end()
+    }
+}
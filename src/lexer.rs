@@ -0,0 +1,217 @@
+//! Single-pass tokenizer for strace lines.
+//!
+//! Everything here borrows from the input line; nothing allocates except the
+//! `Vec` of argument slices. Strings (including `\"` escapes and a trailing
+//! `...` truncation marker), `{...}`/`[...]`/`(...)` nesting, `<...>` fd
+//! annotations and `/* ... */` comments are each skipped as a unit, so commas
+//! and parentheses inside them never split an argument.
+
+/// Index just past the string literal starting at `i` (which must be `"`),
+/// including any `...` truncation marker.
+fn skip_string(b: &[u8], mut i: usize) -> usize {
+    i += 1;
+    while i < b.len() {
+        match b[i] {
+            b'\\' => i += 2,
+            b'"' => {
+                i += 1;
+                if b[i..].starts_with(b"...") {
+                    i += 3;
+                }
+                return i;
+            }
+            _ => i += 1,
+        }
+    }
+    b.len()
+}
+
+/// Index just past the `<...>` annotation starting at `i`, stepping over
+/// backslash escapes.
+fn skip_annotation(b: &[u8], mut i: usize) -> usize {
+    i += 1;
+    while i < b.len() {
+        match b[i] {
+            b'\\' => i += 2,
+            b'>' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    b.len()
+}
+
+fn skip_until(b: &[u8], i: usize, end: &[u8]) -> usize {
+    b[i..]
+        .windows(end.len())
+        .position(|w| w == end)
+        .map_or(b.len(), |p| i + p + end.len())
+}
+
+/// Visit every top-level byte of `s` (outside strings, brackets, annotations
+/// and comments) until `f` returns `true`; returns that byte's index.
+fn scan_top_level(s: &str, mut f: impl FnMut(usize, u8) -> bool) -> Option<usize> {
+    let b = s.as_bytes();
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < b.len() {
+        match b[i] {
+            b'"' => {
+                i = skip_string(b, i);
+                continue;
+            }
+            b'<' => {
+                i = skip_annotation(b, i);
+                continue;
+            }
+            b'/' if b[i..].starts_with(b"/*") => {
+                i = skip_until(b, i + 2, b"*/");
+                continue;
+            }
+            b'{' | b'[' | b'(' => depth += 1,
+            b'}' | b']' | b')' if depth > 0 => depth -= 1,
+            c if depth == 0 && f(i, c) => return Some(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Split an argument list at top-level commas. Each argument is trimmed.
+pub fn split_args(s: &str) -> Vec<&str> {
+    let mut args = Vec::new();
+    let mut start = 0;
+    scan_top_level(s, |i, c| {
+        if c == b',' {
+            args.push(s[start..i].trim());
+            start = i + 1;
+        }
+        false
+    });
+    let last = s[start..].trim();
+    if !last.is_empty() || !args.is_empty() {
+        args.push(last);
+    }
+    args
+}
+
+/// The first argument and everything after its comma.
+pub fn take_first_arg(s: &str) -> (&str, &str) {
+    match scan_top_level(s, |_, c| c == b',') {
+        Some(i) => (s[..i].trim(), &s[i + 1..]),
+        None => (s.trim(), ""),
+    }
+}
+
+/// Start of a `/* ... */` comment that ends `s`, ignoring comment markers
+/// inside strings.
+pub fn trailing_comment(s: &str) -> Option<usize> {
+    let b = s.as_bytes();
+    let mut i = 0;
+    while i < b.len() {
+        match b[i] {
+            b'"' => i = skip_string(b, i),
+            b'/' if b[i..].starts_with(b"/*") => {
+                let end = skip_until(b, i + 2, b"*/");
+                if end == b.len() && s.ends_with("*/") {
+                    return Some(i);
+                }
+                i = end;
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Split `pid rest` into the pid digits and the rest of the line.
+pub fn split_pid(l: &str) -> Option<(&str, &str)> {
+    let end = l.bytes().position(|c| !c.is_ascii_digit()).unwrap_or(l.len());
+    if end == 0 {
+        return None;
+    }
+    Some((&l[..end], l[end..].trim_start()))
+}
+
+/// Split `name(args) = ret` into its three parts. The closing parenthesis is
+/// found by matching, not by searching backwards, so `)` and `=` inside
+/// arguments are harmless.
+pub fn split_call(l: &str) -> Option<(&str, &str, &str)> {
+    let lparen = l.find('(')?;
+    let args_start = lparen + 1;
+    let rparen = args_start + scan_top_level(&l[args_start..], |_, c| c == b')')?;
+    let rest = l[rparen + 1..].trim_start();
+    let ret = rest.strip_prefix('=')?;
+    Some((&l[..lparen], &l[args_start..rparen], ret))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaped_quotes_stay_in_their_string() {
+        let args = split_args(r#""a\", b", "c\\", 1"#);
+        assert_eq!(args, vec![r#""a\", b""#, r#""c\\""#, "1"]);
+    }
+
+    #[test]
+    fn truncated_string_is_one_argument() {
+        assert_eq!(split_args(r#""abc"..., 3"#), vec![r#""abc"..."#, "3"]);
+    }
+
+    #[test]
+    fn nested_brackets_do_not_split() {
+        let args = split_args("3, {st_mode=S_IFREG|0644, st_size=[1, {2, 3}]}, [{a, b}], (x, y)");
+        assert_eq!(args, vec!["3", "{st_mode=S_IFREG|0644, st_size=[1, {2, 3}]}", "[{a, b}]", "(x, y)"]);
+    }
+
+    #[test]
+    fn comments_do_not_split() {
+        let args = split_args(r#""/bin/ls", ["ls"], 0x7ffd /* 20 vars, 1 more */"#);
+        assert_eq!(args, vec![r#""/bin/ls""#, r#"["ls"]"#, "0x7ffd /* 20 vars, 1 more */"]);
+    }
+
+    #[test]
+    fn fd_annotations_do_not_split() {
+        assert_eq!(split_args("3</a, b (c)>, 1"), vec!["3</a, b (c)>", "1"]);
+        assert_eq!(split_args(r"3</a\>, b>, 1"), vec![r"3</a\>, b>", "1"]);
+    }
+
+    #[test]
+    fn empty_argument_list() {
+        assert!(split_args("").is_empty());
+        assert_eq!(split_args("a, "), vec!["a", ""]);
+    }
+
+    #[test]
+    fn first_arg_split() {
+        assert_eq!(take_first_arg("{a, b}, c, d"), ("{a, b}", " c, d"));
+        assert_eq!(take_first_arg("only"), ("only", ""));
+    }
+
+    #[test]
+    fn trailing_comment_found_outside_strings() {
+        let s = r#"0x7ffd /* 20 vars */"#;
+        assert_eq!(trailing_comment(s), Some(7));
+        assert_eq!(trailing_comment(r#""/* not */""#), None);
+        assert_eq!(trailing_comment("/* a */ 3"), None);
+    }
+
+    #[test]
+    fn pid_split() {
+        assert_eq!(split_pid("123  open(...) = 3"), Some(("123", "open(...) = 3")));
+        assert_eq!(split_pid("open()"), None);
+    }
+
+    #[test]
+    fn call_split_matches_parentheses() {
+        let l = r#"openat(AT_FDCWD</a (1)>, "x) = 1", O_RDONLY) = 3</a (1)/x) = 1>"#;
+        assert_eq!(
+            split_call(l),
+            Some(("openat", r#"AT_FDCWD</a (1)>, "x) = 1", O_RDONLY"#, " 3</a (1)/x) = 1>"))
+        );
+        assert_eq!(split_call("exit_group(0) = ?"), Some(("exit_group", "0", " ?")));
+        assert_eq!(split_call("exit_group(0"), None);
+    }
+}
@@ -0,0 +1,134 @@
+//! Parser for `strace -f -y` logs that recovers the files each traced
+//! command read and wrote.
+//!
+//! ```no_run
+//! let lines = std::fs::read_to_string("trace.log").unwrap();
+//! let lines: Vec<&str> = lines.lines().collect();
+//! let (read_set, write_set) = strace_parse::read_write_sets(&lines).unwrap();
+//! ```
+//!
+//! Large or live traces can be streamed instead of read into memory:
+//!
+//! ```no_run
+//! let stdin = std::io::stdin().lock();
+//! let mut events = strace_parse::TraceParser::new().stream(stdin);
+//! for e in events.by_ref() {
+//!     println!("{:?}", e.unwrap());
+//! }
+//! let (read_set, write_set) = events.rw_sets().clone().into_sets();
+//! ```
+
+pub mod args;
+mod audit;
+mod context;
+mod error;
+mod event;
+pub mod exclude;
+mod files;
+pub mod flags;
+pub mod lexer;
+pub mod output;
+mod parse;
+mod process;
+mod rwset;
+mod stream;
+#[cfg(unix)]
+pub mod strace;
+pub mod table;
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub mod tracer;
+
+use std::collections::HashSet;
+
+pub use audit::{audit_syscalls, SyscallAudit, UnknownSyscall};
+pub use context::{Context, MAX_DIAGNOSTICS};
+pub use error::{ErrorMode, ParseError, ParseErrorKind, StreamError, TableError};
+pub use event::{parse_line_events, Access, AccessEvent};
+pub use files::{RFile, RwFile, WFile};
+pub use process::{Exec, Process, ProcessTree};
+pub use parse::{
+    parse_call, parse_line, parse_record, parse_signal, parse_syscall, ExitStatus, LineResult, Record, Signal, SyscallLine,
+    IGNORE_SET, R_FD_PATH_SET, R_FD_SET, R_FIRST_PATH_SET, W_FD_PATH_SET, W_FD_SET, W_FIRST_PATH_SET,
+};
+pub use rwset::{gather_rw_sets, parse_and_gather_cmd_rw_sets, parse_exit_code, ByteCount, CommandSets, PidSets, RwSetBuilder};
+pub use stream::EventStream;
+
+/// Line-at-a-time front end over a [`Context`].
+#[derive(Debug, Default)]
+pub struct TraceParser {
+    ctx: Context,
+}
+
+impl TraceParser {
+    /// Parser whose pids start out in the process's current directory.
+    pub fn new() -> TraceParser {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        TraceParser::with_cwd(&cwd.to_string_lossy())
+    }
+
+    /// Parser whose pids start out in `cwd` until they `chdir`.
+    pub fn with_cwd(cwd: &str) -> TraceParser {
+        let mut ctx = Context::new();
+        ctx.set_dir(cwd, None);
+        TraceParser { ctx }
+    }
+
+    /// Classify the syscalls in `table` by its rules instead of the
+    /// built-in tables.
+    pub fn syscall_table(mut self, table: table::SyscallTable) -> TraceParser {
+        self.ctx.set_syscall_table(table);
+        self
+    }
+
+    /// Set how [`TraceParser::read_write_sets`] treats bad lines.
+    pub fn error_mode(mut self, mode: ErrorMode) -> TraceParser {
+        self.ctx.set_error_mode(mode);
+        self
+    }
+
+    pub fn parse_line(&mut self, l: &str) -> Result<LineResult, ParseError> {
+        parse_line(l, &mut self.ctx)
+    }
+
+    pub fn parse_record(&mut self, l: &str) -> Result<Record, ParseError> {
+        parse_record(l, &mut self.ctx)
+    }
+
+    pub fn parse_call(&mut self, call: SyscallLine, args: &[args::Arg]) -> Result<Record, ParseError> {
+        parse_call(call, args, &mut self.ctx)
+    }
+
+    pub fn parse_line_events(&mut self, l: &str) -> Result<Vec<AccessEvent>, ParseError> {
+        parse_line_events(l, &mut self.ctx)
+    }
+
+    pub fn read_write_sets<S: AsRef<str>>(&mut self, trace_object: &[S]) -> Result<(HashSet<String>, HashSet<String>), ParseError> {
+        parse_and_gather_cmd_rw_sets(trace_object, &mut self.ctx)
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.ctx
+    }
+
+    pub fn context_mut(&mut self) -> &mut Context {
+        &mut self.ctx
+    }
+
+    /// Consume the parser and stream access events from `reader`.
+    pub fn stream<R: std::io::BufRead>(self, reader: R) -> EventStream<R> {
+        EventStream::new(reader, self.ctx)
+    }
+
+    /// Errors skipped over so far in [`ErrorMode::Lenient`], up to
+    /// [`MAX_DIAGNOSTICS`]; see [`Context::dropped_diagnostics`].
+    pub fn diagnostics(&self) -> &[ParseError] {
+        self.ctx.diagnostics()
+    }
+}
+
+/// Read and write sets of a whole trace, resolving relative paths against
+/// the current directory. Same as [`parse_and_gather_cmd_rw_sets`] with a
+/// fresh, lenient [`Context`].
+pub fn read_write_sets<S: AsRef<str>>(trace_object: &[S]) -> Result<(HashSet<String>, HashSet<String>), ParseError> {
+    TraceParser::new().read_write_sets(trace_object)
+}
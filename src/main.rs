@@ -1,728 +1,671 @@
-use phf::phf_set;
-use regex::{bytes, Regex};
-use std::fs::FileTimes;
-use std::io::Bytes;
-use std::path::Path;
-use std::collections::{HashMap, HashSet};
-use std::{fs, os};
-use std::path::PathBuf;
-use unescape;
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
 use std::fs::File;
-use std::io::{self, BufRead};
-
-
-pub static R_FIRST_PATH_SET: phf::Set<&'static str> = phf_set! {
-    "execve", "stat", "lstat", "access", "statfs",
-    "readlink", "getxattr", "lgetxattr", "llistxattr",
-};
-
-pub static W_FIRST_PATH_SET: phf::Set<&'static str> = phf_set! {
-    "mkdir", "rmdir", "truncate", "creat", "chmod", "chown",
-    "lchown", "utime", "mknod", "utimes", "acct", "unlink",
-    "setxattr", "removexattr",
-};
-
-pub static R_FD_PATH_SET: phf::Set<&'static str> = phf_set! {
-    "fstatat", "newfstatat", "statx", "name_to_handle_at",
-    "readlinkat", "faccessat", "execveat", "faccessat2",
-};
-
-pub static W_FD_PATH_SET: phf::Set<&'static str> = phf_set! {
-    "unlinkat", "utimensat", "mkdirat", "mknodat", "fchownat", "futimeat",
-    "linkat", "fchmodat",
-};
-
-pub static IGNORE_SET: phf::Set<&'static str> = phf_set! {
-    "getpid", "getcwd",
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
+use std::process;
+
+use serde::Serialize;
+use strace_parse::exclude::{PathRule, PathRules, RuleAction, Suppressed};
+use strace_parse::output::{self, Format, RwSets};
+#[cfg(unix)]
+use strace_parse::strace::{Strace, DEFAULT_STRING_LIMIT};
+use strace_parse::table::SyscallTable;
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+use strace_parse::tracer::{TraceEvent, Tracer};
+use strace_parse::{
+    Access, AccessEvent, ErrorMode, Exec, ParseError, Process, ProcessTree, Record, RwSetBuilder, Signal, SyscallAudit,
+    TraceParser,
 };
 
-const RE:  &str =  "((?:\"[^\"\n]*\"|<[^>\n]*>|{[^}\n]*}|[^,\n])+)";
-
-fn split_args(s: &str) -> Vec<String>{
-    let re = Regex::new(RE).unwrap();
-    re.captures_iter(s)
-        .map(|cap| cap[1].to_string())
-        .collect()
-}
-
-fn take_first_args(s: &str) -> (&str, &str) {
-    let re = Regex::new(RE).unwrap();
-    let mat = re.find(s).unwrap();
-    let first = mat.as_str();
-    let rest_idx = mat.end() +1;
-    let rest = if rest_idx <= s.len() {
-        &s[rest_idx..]
-    } else {
-        ""
-    };
-
-    (first, rest)
-}
-
-
-#[derive(Debug, Clone, PartialEq)]
-struct ExitStatus {
-    exitcode: i32,
-}
-
-fn parse_info(l: &str) -> ExitStatus {
-    if l.contains("exited") {
-        let start = "+++ exited with ".len();
-        let end = l.len() - " +++".len();
-        let exitcode_str = &l[start..end];
-        let exitcode = exitcode_str.trim().parse::<i32>().expect("Error parsing exit code");
-        ExitStatus { exitcode }
-    } else if l.contains("Killed") {
-        ExitStatus { exitcode: -1 }
-    } else {
-        panic!("Unhandled input: {l}");
-    }
-}
-#[derive(Debug, Clone, PartialEq)]
-struct RFile {
-     
-    fname: String,
-
-} 
-impl RFile {
-    fn new(path: &str) -> Self {
-        RFile {
-            fname: path.to_string(),
-        }
-    }
-
-    fn closure(&self) -> Vec<RFile> {
-
-        let mut all_files = vec![self.clone()];
-
-        if !self.fname.starts_with('/') {
-            return all_files;
-        }
-
-        let mut current_path = Path::new(&self.fname);
-        let mut depth = 0;
-
-        while let Some(parent) = current_path.parent() {
-            if parent == Path::new("") || parent == Path::new("/") {
-                break;
-            }
-
-            all_files.push(RFile {
-                fname: parent.display().to_string(),
-            });
-
-            current_path = parent;
-            depth += 1;
-            if depth > 512 {
-                panic!("Path closure exceeded 512 levels");
-            }
+const USAGE: &str = "\
+Usage: strace-parse [command] [options] <file|->
+       strace-parse run [command] [options] -- <program> [args...]
+       strace-parse trace [command] [options] -- <program> [args...]
+
+Commands:
+  events            every file access, one per line (default)
+  rwset             read and write sets of the whole trace
+  exitcode          exit code of the first (or --pid) process, 128+signal if killed
+  stats             counts of lines, calls, accesses and syscalls
+  unknown-syscalls  syscalls the parser could not classify
+  tree              every pid with its parent, programs, signals received
+                    and how it ended (or that it was still running)
+
+`run` traces <program> itself with ptrace instead of reading a strace log;
+`trace` runs it under `strace -f -y -s <N>` and parses the output as it is
+produced. Both then act like the command after them (`events` by default)
+and exit with the program's exit code, or 128 plus the signal number if a
+signal killed it.
+
+Options:
+  --strict                     stop at the first malformed line
+  --format text|json|jsonl     output format (default text)
+  --pid <pid>                  only this pid (repeatable)
+  --syscall <name>             only this syscall (repeatable)
+  --path <prefix>              only paths under this prefix (repeatable)
+  --access read|write          only reads or only writes
+  --written-only               write set only has files bytes were written to
+  --by-pid                     rwset: the sets of each pid as well
+  --subtree                    rwset: the sets of each pid and everything it
+                               started (implies --by-pid)
+  --by-command                 rwset: the sets of each program run (a pid
+                               between execs), with its command line
+  --table <file>               syscall classification overrides, TOML or
+                               .json (repeatable, later files win)
+  --exclude [read:|write:]<p>  keep paths matching <p> out of the sets
+  --include [read:|write:]<p>  keep them in despite a later exclude
+                               (repeatable; the first matching rule wins)
+  --rules <file>               more include/exclude rules, TOML or .json,
+                               tried after the flags
+  --no-default-excludes        do not drop /proc, /sys, /dev and
+                               /tmp/pash_spec* after all other rules
+  --strace <path>              strace binary for `trace` (default strace)
+  --string-limit <n>           strace -s value for `trace` (default 4096)";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Command {
+    Events,
+    RwSet,
+    ExitCode,
+    Stats,
+    UnknownSyscalls,
+    Tree,
+}
+
+impl Command {
+    fn from_name(s: &str) -> Option<Command> {
+        match s {
+            "events" => Some(Command::Events),
+            "rwset" => Some(Command::RwSet),
+            "exitcode" => Some(Command::ExitCode),
+            "stats" => Some(Command::Stats),
+            "unknown-syscalls" => Some(Command::UnknownSyscalls),
+            "tree" => Some(Command::Tree),
+            _ => None,
         }
-
-        all_files
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-struct WFile {
-     
-    fname: String,
-
-} 
-impl WFile {
-    fn new(path: &str) -> Self {
-        // let path_buf = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
-        //might need to revert to this later
-        WFile {
-            // fname: path_buf.to_str().unwrap().to_string(),
-            fname: path.to_string()
-        }
-    }
-
-    fn closure(&self) -> Vec<WFile> {
-
-        let mut all_files = vec![self.clone()];
-
-        if !self.fname.starts_with('/') {
-            return all_files;
-        }
-
-        let mut current_path = Path::new(&self.fname);
-        let mut depth = 0;
-
-        while let Some(parent) = current_path.parent() {
-            if parent == Path::new("") || parent == Path::new("/") {
-                break;
-            }
-
-            all_files.push(WFile {
-                fname: parent.display().to_string(),
-            });
-
-            current_path = parent;
-            depth += 1;
-            if depth > 512 {
-                panic!("Path closure exceeded 512 levels");
-            }
-        }
-
-        all_files
-    }
-}
-#[derive(Debug, Clone, PartialEq)]
-enum rwFile {
-    rfile(RFile),
-    wfile(WFile)
-}
-struct Context {
-    line_dict: HashMap<i32, String>,
-    curdir_dict: HashMap<i32, String>,
-    pid_group_dict: HashMap<i32, i32>,
-    curdir_fallback: String,
+#[derive(Debug, Default)]
+struct Filter {
+    pids: Vec<i32>,
+    syscalls: Vec<String>,
+    paths: Vec<String>,
+    access: Option<Access>,
 }
 
-impl Context {
-    fn new()->Context {
-        let line_dict = HashMap::new();
-        let curdir_dict = HashMap::new();
-        let pid_group_dict = HashMap::new();
-        let path = String::from("");
-        Context { 
-            line_dict: line_dict, 
-            curdir_dict: curdir_dict, 
-            pid_group_dict: pid_group_dict,
-            curdir_fallback: path 
-        }
+impl Filter {
+    fn keep_pid(&self, pid: i32) -> bool {
+        self.pids.is_empty() || self.pids.contains(&pid)
     }
 
-    fn do_clone(&mut self, parent: i32, child: i32){
-        self.pid_group_dict.insert(child, parent);
+    fn keep_syscall(&self, syscall: &str) -> bool {
+        self.syscalls.is_empty() || self.syscalls.iter().any(|s| s == syscall)
     }
 
-    fn set_dir(&mut self, path: & str, pid: Option<i32>){
-        self.curdir_fallback = path.to_string().clone();
-        let mut pid = pid.unwrap_or(-1);
-        if pid != -1 && self.pid_group_dict.contains_key(&pid) {
-            pid = self.pid_group_dict.get(&pid).copied().unwrap_or(-1);
-        }
-        if pid != -1 {
-            self.curdir_dict.insert(pid, path.to_string().clone());
-        }
-    }
-
-    fn get_dir(&mut self, pid: i32) -> String{
-        let mut pid = pid;
-        if self.pid_group_dict.contains_key(& pid) {
-            pid = self.pid_group_dict.get(& pid).copied().unwrap_or(-1);
-        }
-
-        if !self.pid_group_dict.contains_key(& pid) {
-            let temp = self.curdir_fallback.clone();
-            self.curdir_dict.insert(pid, temp );
-        }
-
-        return self.curdir_dict.get(& pid).cloned().expect("Unexpected error")
-    }
-
-    fn push_half_line(& mut self, pid: i32, l: & str){
-        let s = match l.find("<unfinished") {
-            Some(pos) => &l[0..pos],
-            None => ""
-        };
-        let s = s.trim().to_string();
-        self.line_dict.insert(pid, s);
-        
-    }
-
-    fn pop_complete_line(&mut self, pid: i32, l: &str) -> String {
-        let index = match l.find("resumed>") {
-            Some(pos) => pos + "resumed>".len(),
-            None => return "ERROR".to_string()
-        };
-        let line = self.line_dict.get(&pid).expect("not found");
-        let line = line.clone() + &l[index..];
-        self.line_dict.remove(&pid);
-        return line;
-
-
-
+    fn keep(&self, e: &AccessEvent) -> bool {
+        self.keep_pid(e.pid)
+            && self.keep_syscall(&e.syscall)
+            && (self.paths.is_empty() || self.paths.iter().any(|p| e.path.starts_with(p.as_str())))
+            && self.access.is_none_or(|a| a == e.access)
     }
 }
 
-fn parse_string(s: &str) -> String {
-    let mut s = s.trim();
-
-    if s.eq("NULL") {
-       return String::new()
-    }
-
-    if s.ends_with("...") {
-        let l = s.len()-"...".len();
-        s = &s[..l];
-    }
-    if !s.starts_with('"') || !s.ends_with('"') {
-        panic!("Unexpected behaviour");
-    }
-
-    s = &s[1..s.len()-1];
-
-    unescape::unescape(s).unwrap_or_else(|| s.to_string())
-}   
-
-fn between(s: &str, d1: &str, d2: &str) -> Option<(usize, usize)> {
-    let a = s.find(d1)?;
-    let b = s.rfind(d2)?;
-    Some((a+ d1.len(), b))
-}
-
-fn is_absolute_path(path: &str) -> bool{
-    !path.is_empty() && path.starts_with('/')
+enum Input {
+    File(String),
+    #[cfg_attr(not(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"))), allow(dead_code))]
+    Run(Vec<String>),
+    /// `string_limit` is `None` for `DEFAULT_STRING_LIMIT`.
+    #[cfg_attr(not(unix), allow(dead_code))]
+    Trace { program: Vec<String>, strace: String, string_limit: Option<usize> },
 }
 
-fn is_ret_err(ret: &str) -> bool{
-    ret.trim().starts_with('-')
+struct Options {
+    command: Command,
+    mode: ErrorMode,
+    written_only: bool,
+    by_pid: bool,
+    subtree: bool,
+    by_command: bool,
+    tables: Vec<String>,
+    path_rules: Vec<PathRule>,
+    rules_files: Vec<String>,
+    default_excludes: bool,
+    format: Format,
+    filter: Filter,
+    input: Input,
 }
 
-fn get_ret_file_path(ret :&str) -> String{
-    if !is_ret_err(ret) {
-        panic!("Unexpected behaviour passed into function get_ret_file_path")
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let (launch, args) = match args.first().map(String::as_str) {
+        Some(l @ ("run" | "trace")) => (Some(l), &args[1..]),
+        _ => (None, args),
     };
-    let ret = ret.trim();
-    let start = ret.find("<").unwrap() +1;
-    let end = ret.rfind(">").unwrap() +1;
-
-    (&ret[start..end]).to_string()
-}
-
-fn convert_absolute(cur_dir: &Path, path: &str) -> PathBuf {
-    let p = Path::new(path);
-    if is_absolute_path(path){
-        p.to_path_buf()
-    } else {
-        cur_dir.join(p)
-    }
-
-}
-// fn get_path_first_path(pid: i32, args: &str, ctx: &mut Context) -> PathBuf{
-//     let a = parse_string(&split_args(args)[1]);
-
-//     convert_absolute(Path::new(& ctx.get_dir(pid)), &a)
-// }
-
-fn get_path_first_path(pid: i32, args: &str, ctx: &mut Context) -> Result<PathBuf, &'static str> {
-    let a = parse_string(&split_args(args)[0]); 
-
-    let dir = ctx.get_dir(pid);
-    let abs_path = convert_absolute(Path::new(&dir), &a);
-
-    if abs_path.as_os_str().is_empty() {
-        Err("path resolution failed")
-    } else {
-        Ok(abs_path)
-    }
-}
-
-
-fn parse_r_first_path(pid: i32, args: &str, ret: &str, ctx: &mut Context) -> rwFile {
-    rwFile::rfile(RFile::new(&get_path_first_path(pid, args, ctx)
-        .unwrap()
-        .to_str()
-        .expect("failed to create rfile, parse_r_first_path")))
-}
-
-fn parse_w_first_path(pid: i32, args: &str, ret: &str, ctx: &mut Context) -> rwFile {
-    if is_ret_err(ret) {
-        rwFile::rfile(RFile::new(&get_path_first_path(pid, args, ctx)
-            .unwrap()
-            .to_str()
-            .expect("failed to create rfile, parse_r_first_path")))
-    }
-    else {
-        rwFile::wfile(WFile::new(&get_path_first_path(pid, args, ctx)
-            .unwrap()
-            .to_str()
-            .expect("failed to create rfile, parse_r_first_path")))
+    let (command, args) = match args.first().and_then(|a| Command::from_name(a)) {
+        Some(c) => (c, &args[1..]),
+        None => (Command::Events, args),
+    };
+    let (args, program) = match args.iter().position(|a| a == "--") {
+        Some(i) if launch.is_some() => (&args[..i], args[i + 1..].to_vec()),
+        _ => (args, vec![]),
+    };
+    let mut mode = ErrorMode::Lenient;
+    let mut written_only = false;
+    let mut by_pid = false;
+    let mut subtree = false;
+    let mut by_command = false;
+    let mut tables = Vec::new();
+    let mut path_rules = Vec::new();
+    let mut rules_files = Vec::new();
+    let mut default_excludes = true;
+    let mut format = Format::Text;
+    let mut filter = Filter::default();
+    let mut fname = None;
+    let mut strace = "strace".to_string();
+    let mut string_limit = None;
+    let mut it = args.iter();
+    while let Some(a) = it.next() {
+        let (flag, inline) = match a.split_once('=') {
+            Some((f, v)) if a.starts_with("--") => (f, Some(v.to_string())),
+            _ => (a.as_str(), None),
+        };
+        let mut value = || {
+            inline.clone()
+                .or_else(|| it.next().cloned())
+                .ok_or(format!("{flag} needs a value"))
+        };
+        match flag {
+            "--strict" => mode = ErrorMode::Strict,
+            "--written-only" => written_only = true,
+            "--by-pid" => by_pid = true,
+            "--subtree" => subtree = true,
+            "--by-command" => by_command = true,
+            "--table" => tables.push(value()?),
+            "--exclude" => path_rules.push(PathRule::parse(RuleAction::Exclude, &value()?)),
+            "--include" => path_rules.push(PathRule::parse(RuleAction::Include, &value()?)),
+            "--rules" => rules_files.push(value()?),
+            "--no-default-excludes" => default_excludes = false,
+            "--format" => format = value()?.parse()?,
+            "--pid" => {
+                let v = value()?;
+                filter.pids.push(v.parse().map_err(|_| format!("invalid pid `{v}`"))?);
+            }
+            "--syscall" => filter.syscalls.push(value()?),
+            "--path" => filter.paths.push(value()?),
+            "--access" => filter.access = Some(value()?.parse()?),
+            "--strace" => strace = value()?,
+            "-s" | "--string-limit" => {
+                let v = value()?;
+                string_limit = Some(v.parse().map_err(|_| format!("invalid string limit `{v}`"))?);
+            }
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                process::exit(0);
+            }
+            _ if flag.starts_with("--") => return Err(format!("unknown option `{flag}`")),
+            _ if fname.is_none() => fname = Some(a.clone()),
+            _ => return Err(format!("unexpected argument `{a}`")),
+        }
     }
-}
-
-enum argPos {
-    single(i32),
-    multiple(Vec<i32>,)
-}
-
-//potential error in the python version? fixxed here
-fn get_path_at(pid: i32, positions: argPos, args:  &str, ctx: &mut Context) -> Vec<PathBuf>{
-    let args = split_args(args);
-
-    match positions {
-        argPos::single(i) =>{
-            let m = &args[1];
-            vec![convert_absolute(Path::new(& ctx.get_dir(pid)), &parse_string(m))]
+    if launch.is_some() {
+        if let Some(f) = fname {
+            return Err(format!("unexpected argument `{f}` (put the program after `--`)"));
         }
-        argPos::multiple(l) => {
-            let mut res: Vec<PathBuf> = Vec::new();
-            for arg in &args {
-                res.push(convert_absolute(Path::new(& ctx.get_dir(pid)), &parse_string(arg)));
-            }
-            res
+        if program.is_empty() {
+            return Err("missing program after `--`".to_string());
         }
     }
+    let input = match launch {
+        Some("run") => Input::Run(program),
+        Some(_) => Input::Trace { program, strace, string_limit },
+        None => Input::File(fname.ok_or("missing filename")?),
+    };
+    Ok(Options {
+        command,
+        mode,
+        written_only,
+        by_pid: by_pid || subtree,
+        subtree,
+        by_command,
+        tables,
+        path_rules,
+        rules_files,
+        default_excludes,
+        format,
+        filter,
+        input,
+    })
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Stats {
+    lines: usize,
+    calls: usize,
+    unclassified_calls: usize,
+    accesses: usize,
+    reads: usize,
+    writes: usize,
+    bytes_read: u64,
+    bytes_written: u64,
+    errors: usize,
+    pids: usize,
+    syscalls: BTreeMap<String, usize>,
+    suppressed: Vec<Suppressed>,
+}
+
+#[derive(Serialize)]
+struct ExitCode {
+    pid: i32,
+    exitcode: i32,
 }
 
-fn parse_rename(pid: i32, args: &str, ret: &str, ctx: &mut Context) -> Vec<rwFile> {
-    let paths = get_path_at(pid, argPos::multiple(vec![0,1]), args, ctx);
-    vec![
-        rwFile::wfile(WFile::new(&paths[0].to_str()
-            .expect("failed to create rfile, parse_r_first_path"))),
-        rwFile::wfile(WFile::new(&paths[1].to_str()
-            .expect("failed to create rfile, parse_r_first_path"))),
-        ]
-}
-
-fn parse_link(pid: i32, args: &str, ret: &str, ctx: &mut Context) -> Vec<rwFile> {
-    let paths = get_path_at(pid, argPos::multiple(vec![0,1]), args, ctx);
-    vec![
-        rwFile::rfile(RFile::new(&paths[0].to_str()
-            .expect("failed to create rfile, parse_r_first_path"))),
-        rwFile::wfile(WFile::new(&paths[1].to_str()
-            .expect("failed to create rfile, parse_r_first_path"))),
-        ]
-
-}
-
-fn parse_chdir(pid: i32, args: &str, ret: &str, ctx: &mut Context) -> rwFile {
-    let new_path = get_path_first_path(pid, args, ctx).expect("failed parse chdir in get path first path");
-    if !is_ret_err(ret) {
-        ctx.set_dir((new_path.to_str().expect("failed to set to path")), Some(pid));
-    }
-
-    rwFile::rfile(RFile::new(&new_path.to_str().expect("failed in creating file in parse chdir")))
-
+#[derive(Serialize)]
+struct Unknown<'a> {
+    syscalls: &'a SyscallAudit,
 }
 
-fn handle_open_flag(flags: &str) -> char{
-    if flags.contains("O_RDONLY") {
-        return 'r'
-    }
-    'w'
+#[derive(Serialize)]
+struct TreeEntry<'a> {
+    #[serde(flatten)]
+    process: &'a Process,
+    running: bool,
 }
 
-fn handle_open_common(total_path: PathBuf, flags: &str, ret :&str) -> Vec<rwFile>{
-    let file_path =total_path.to_str().expect("error in handle open common returning rfile");
-    if is_ret_err(ret){
-        vec![rwFile::rfile(RFile::new(file_path))]
-    }
-    else if handle_open_flag(flags) == 'r' {
-        vec![rwFile::rfile(RFile::new(file_path)), rwFile::rfile(RFile::new(&get_ret_file_path(ret)))]
-    }
-    else {
-        vec![rwFile::wfile(WFile::new(file_path)), rwFile::wfile(WFile::new(&get_ret_file_path(ret)))]
-
-    }
+#[derive(Serialize)]
+struct Tree<'a> {
+    processes: Vec<TreeEntry<'a>>,
 }
 
-fn parse_openat(args:  &str, ret :&str) -> Option< Vec<rwFile>>{
-    let args = split_args(args);
-    let path = parse_string(&args[1]);
-    let dfd = &args[1];
-    let p = Path::new(&path);
-    let flags = &args[2];
-    if path.len() == 0{
-        return None
+/// `pid` and its children, one line each, indented by depth:
+/// `101 killed by SIGPIPE: cat foo; signals SIGPIPE from 102`.
+fn write_tree<W: Write>(out: &mut W, tree: &ProcessTree, pid: i32, depth: usize) -> io::Result<()> {
+    let Some(p) = tree.get(pid) else {
+        return Ok(());
     };
-    let total_path = if is_absolute_path(&path) {
-        Path::new(&path.clone()).to_path_buf()
+    let thread = if p.is_thread() { " thread" } else { "" };
+    let status = p.exit.as_ref().map_or("running".to_string(), |s| s.to_string());
+    let commands = if !p.execs.is_empty() {
+        p.execs.iter().map(Exec::command_line).collect::<Vec<_>>().join(" => ")
     } else {
-        let begin = dfd.find("<").unwrap() +1;
-        let end = (&dfd[begin..]).find(">").unwrap() + begin;
-        let pwd = &dfd[begin..end];
-        Path::new(pwd).join(&path)
-    };
-    Some(handle_open_common(total_path, flags, ret))
-}
-
-fn parse_open(pid:i32, args:  &str, ret :&str, ctx: &mut Context) -> Option<Vec<rwFile>>{
-    let total_path = match get_path_first_path(pid, args, ctx) {
-        Ok(path) => path,
-        Err(_) => return None
+        p.exec.as_ref().map_or("?".to_string(), |e| format!("({})", e.command_line()))
     };
-
-    let flags = &split_args(&args)[1];
-
-    Some(handle_open_common(total_path, flags, ret))
-
-}
-
-fn get_path_from_fd_path(args:  &str) -> PathBuf{
-    let t = &split_args(args);
-
-    let a0 = &t[0];
-    let a1 = &t[1];
-
-    let a1 = parse_string(a1);
-    if a1.len() != 0 && a1.starts_with('/') {
-        Path::new(&a1).to_path_buf()
-    } else {
-        let begin = a0.find("<").unwrap() +1;
-        let end = (&a0[begin..]).find(">").unwrap() + begin;
-        let a0 = &a0[begin..end];
-        Path::new(&a1).join(&a0)
+    write!(out, "{:indent$}{pid}{thread} {status}: {commands}", "", indent = depth * 2)?;
+    if !p.signals.is_empty() {
+        let signals: Vec<String> = p.signals.iter().map(Signal::to_string).collect();
+        write!(out, "; signals {}", signals.join(", "))?;
     }
-
-}
-
-fn parse_renameat(pid:i32, args:  &str, ret :&str, ctx: &mut Context) -> Vec<rwFile> {
-    let path_a = get_path_from_fd_path(args);
-    let second_set = &(split_args(args)[2..].join(","));
-    let path_b = get_path_from_fd_path(second_set);
-    vec![rwFile::wfile(WFile::new(&path_a.to_str().expect("could not turn path A to str in parse rename at"))), 
-        rwFile::wfile(WFile::new(&path_b.to_str().expect("could not turn path B to str in parse rename at")))]
-}
-
-fn parse_r_fd_path(args:  &str, ret :&str) -> rwFile {
-    rwFile::rfile(RFile::new(& get_path_from_fd_path(args).to_str().expect("failed in parse r fd path")))
-}
-
-fn parse_w_fd_path(args:  &str, ret :&str) -> rwFile {
-    if is_ret_err(ret) {
-        rwFile::rfile(RFile::new(& get_path_from_fd_path(args).to_str().expect("failed in parse r fd path")))
-    } else {
-        rwFile::wfile(WFile::new(& get_path_from_fd_path(args).to_str().expect("failed in parse r fd path")))
+    writeln!(out)?;
+    for &child in &p.children {
+        write_tree(out, tree, child, depth + 1)?;
     }
+    Ok(())
 }
 
-fn has_clone_fs(flags: &str) -> bool {
-    if flags.contains("CLONE_FS"){
-        true
-    } else{
-        false
+fn write_event<W: Write>(out: &mut W, format: Format, e: &AccessEvent) -> io::Result<()> {
+    match format {
+        Format::Text => {
+            let access = match e.access {
+                Access::Read => "read",
+                Access::Write => "write",
+            };
+            writeln!(out, "{}\t{}\t{}\t{}\t{}", e.line, e.pid, e.syscall, access, e.path)
+        }
+        Format::Jsonl => output::write_jsonl(out, "access", e),
+        Format::Json => Ok(()),
     }
 }
 
-fn parse_clone(pid:i32, args:  &str, ret :&str, ctx: &mut Context) {
-    let child = match ret.trim().parse() {
-        Ok(num) => num,
-        Err(_) => -1
-    };
-    if child < 0 {
-        return
-    }
-    let arg_list: Vec<String> = split_args(args)
-        .into_iter()
-        .map(|x:String| x.trim().to_string())
-        .collect();
-
-    let flags: String = arg_list
-        .into_iter()
-        .find(|x| x.starts_with("flags="))
-        .map(|x| x["flags=".len()..].to_string()).expect("failed in finding flags in arg list");
-
-    if has_clone_fs(&flags) {
-        ctx.do_clone(pid, child);
+fn write_single<W: Write, T: Serialize>(out: &mut W, format: Format, kind: &str, body: &T) -> io::Result<()> {
+    match format {
+        Format::Jsonl => output::write_jsonl(out, kind, body),
+        _ => output::write_json_tagged(out, kind, body),
     }
 }
 
-
-fn parse_symlinkat(pid:i32, args:  &str, ret :&str) -> rwFile {
-    let t = take_first_args(args);
-    parse_w_fd_path(t.0, t.1)
-}
-
-fn parse_symlink(pid:i32, args:  &str, ret :&str, ctx: &mut Context) -> rwFile {
-    let t = take_first_args(args);
-    parse_w_first_path(pid, t.1, ret, ctx)
+/// One unit of trace input, not yet parsed: a strace line or an event from
+/// the ptrace tracer.
+enum Raw {
+    Line(String),
+    #[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+    Traced(TraceEvent),
 }
 
-fn parse_inotify_add_watch(pid:i32, args:  &str, ret :&str, ctx: &mut Context) -> rwFile {
-    let (_, rest) = take_first_args(args);
-    parse_r_first_path(pid, rest, ret, ctx)
-}
+type Records<'a> = Box<dyn Iterator<Item = io::Result<Raw>> + 'a>;
 
-fn parse_syscall(pid: i32, syscall: &str, args:  &str, ret :&str, ctx: &mut Context)-> Result<Vec<rwFile>,&'static str>{
-    let t: Result<Vec<rwFile>,&str> = match syscall {
-        s if R_FIRST_PATH_SET.contains(s) => Ok(vec![parse_r_first_path(pid, args, ret, ctx)]),
-        s if W_FIRST_PATH_SET.contains(s) => Ok(vec![parse_w_first_path(pid, args, ret, ctx)]),
-        "openat" => Ok(parse_openat(args, ret).expect("failed openat syscall in parse_syscall")),
-        "chdir" => Ok(vec![parse_chdir(pid, args, ret, ctx)]),
-        "open" => Ok(parse_open(pid, args, ret, ctx).expect("failed open syscall in parse_syscall")),
-        s if R_FD_PATH_SET.contains(s) => Ok(vec![parse_r_fd_path(args, ret)]),
-        s if W_FD_PATH_SET.contains(s) => Ok(vec![parse_w_fd_path(args, ret)]),
-        "rename" => Ok(parse_rename(pid, args, ret, ctx)),
-        "renameat" | "renameat2" => Ok(parse_renameat(pid, args, ret, ctx)),
-        "symlinkat" => Ok(vec![parse_symlinkat(pid, args, ret)]),
-        "symlink" | "link" => Ok(vec![parse_symlink(pid, args, ret, ctx)]),
-        "clone" => Err("Parse clone returns nothing"),
-        "inotify_add_watch" => Ok(vec![parse_inotify_add_watch(pid, args, ret, ctx)]),
-        s if IGNORE_SET.contains(s) => return Err("syscall in ignore set"),
-        _ => Err("Unclassified syscall: {syscall}"),
+/// Lines of a log file, or of stdin when `fname` is `-`.
+fn file_records(fname: &str) -> io::Result<Records<'static>> {
+    let reader: Box<dyn BufRead> = if fname == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(io::BufReader::new(File::open(fname)?))
     };
-    t
+    Ok(Box::new(reader.lines().map(|l| l.map(Raw::Line))))
 }
 
-fn strip_pid(l: &str) -> (i32, String){
-    if l.chars().next().unwrap().is_ascii_digit(){
-        let pair: Vec<& str> = l.split(" ").collect();
-        return (pair[0].parse().unwrap(), pair[1..].concat())
-
-    }
-    panic!("expected pid at strip pid")
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn traced_records(tracer: &mut Tracer) -> Records<'_> {
+    Box::new(tracer.map(|e| e.map(Raw::Traced)))
 }
 
-fn handle_info(l: &str) -> (bool, Option<ExitStatus>) {
-    if l.ends_with("+++") {
-        (true, Some(parse_info(l)))
-    }
-    else if l.ends_with("---") {
-        (true, None)
-    }
-    else {
-        (false, None)
-    }
-
-}
-enum parse_line_ret {
-    None,
-    info(Option<ExitStatus>),
-    files(Vec<rwFile>),
+#[cfg(unix)]
+fn strace_records(strace: &mut Strace) -> Records<'_> {
+    Box::new(strace.map(|l| l.map(Raw::Line)))
 }
-fn parse_line(l: &str, ctx: &mut Context) -> parse_line_ret {
-    if l.len() == 0 {
-        return parse_line_ret::None
-    };
-    let (pid, l) = strip_pid(l);
-    if l.len() == 0 {
-        return parse_line_ret::None
-    }
-    let (is_info, info) = handle_info(&l);
-    if is_info {
-        return parse_line_ret::info(info)
-    };
-
-    if l.contains("<unfinished") {
-        ctx.push_half_line(pid, &l);
-        return parse_line_ret::None
-    }
-    let l =  if l.contains("resumed>") {
-        ctx.pop_complete_line(pid, &l)
-    } else {
-        "".to_string()
-    };
 
-    let (lparen, equals) = match (l.find('('), l.rfind('=')) {
-         (Some(l), Some(e)) => (l, e),
-    _ => {
-        return parse_line_ret::None;
+fn parse_raw(parser: &mut TraceParser, raw: Raw) -> Result<Record, ParseError> {
+    match raw {
+        Raw::Line(l) => parser.parse_record(&l),
+        #[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+        Raw::Traced(TraceEvent::Call { call, args }) => parser.parse_call(call, &args),
+        #[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+        Raw::Traced(TraceEvent::Exited { pid, status }) => {
+            parser.context_mut().process_exited(pid, status.clone());
+            Ok(Record::Info { pid, status: Some(status) })
         }
-    };
-
-    let Some(rparen) = (&l[lparen..]).rfind(')') else {
-        return parse_line_ret::None;
-    };
-
-    let syscall = &l[..rparen];
-    let ret = &l[equals+1..];
-    let args = &l[lparen+1..rparen];
-
-    return parse_line_ret::files(parse_syscall(pid, syscall, args, ret, ctx)
-                .expect("failed parse_syscall fn call from parse_line"))
-
-}
-
-fn parse_exit_code(trace_object: &Vec<String>) -> Option<i32>{
-    if trace_object.len() == 0 || trace_object[0].is_empty() {
-        return None
-    };
-
-    let l = &trace_object[0];
-    let (first_pid, _) = strip_pid(l);
-    for l in trace_object {
-        let (pid, tmpl) = strip_pid(l);
-        let (is_info, info) = handle_info(l);
-        if is_info && pid == first_pid && info.is_some() {
-            let temp = info.unwrap();
-            return Some(temp.exitcode);
+        #[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+        Raw::Traced(TraceEvent::Signal { pid, signal }) => {
+            parser.context_mut().signal_received(pid, signal.clone());
+            Ok(Record::Signal { pid, signal })
         }
-    };
-    panic!("No exitcode in parse_exit_code");
+    }
 }
 
-fn parse_and_gather_cmd_rw_sets(trace_object: &Vec<String>, ctx: &mut Context) -> (HashSet<String>, HashSet<String>) {
-    let mut read_set: HashSet<String> = HashSet::new();
-    let mut write_set: HashSet<String> = HashSet::new();
-
-    let mut records: Vec<rwFile> = Vec::new();
+/// Runs the selected command over `records` and returns the exit code of the
+/// first pid in the trace, if it exited, as [`strace_parse::ExitStatus::shell_code`].
+fn process(
+    opts: &Options,
+    rules: PathRules,
+    parser: &mut TraceParser,
+    records: Records,
+    errors: &mut Vec<ParseError>,
+) -> Result<Option<i32>, Box<dyn std::error::Error>> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    // Only a JSON document of events needs them all at once.
+    let keep_events = opts.command == Command::Events && opts.format == Format::Json;
+    let mut events = Vec::new();
+    let mut sets = RwSetBuilder::new()
+        .written_only(opts.written_only)
+        .per_pid(opts.by_pid)
+        .per_command(opts.by_command)
+        .path_rules(rules);
+    let mut stats = Stats::default();
+    let mut pids = BTreeSet::new();
+    let mut unknown = SyscallAudit::new();
+    let mut exit: Option<ExitCode> = None;
+    let mut first_pid = None;
+    let mut root_exit = None;
 
-    for l in trace_object {
-        if let parse_line_ret::files(files) = parse_line(l, ctx) {
-            for f in files {
-                let keep = match &f {
-                    rwFile::rfile(r) => !r.fname.starts_with("/tmp/pash_spec") && !r.fname.starts_with("/dev"),
-                    rwFile::wfile(w) => !w.fname.starts_with("/tmp/pash_spec") && !w.fname.starts_with("/dev"),
+    for record in records {
+        stats.lines += 1;
+        let record = match parse_raw(parser, record?) {
+            Ok(record) => record,
+            Err(e) => {
+                stats.errors += 1;
+                if opts.mode == ErrorMode::Strict {
+                    return Err(e.into());
+                }
+                errors.push(e);
+                continue;
+            }
+        };
+        match record {
+            Record::Skip => {}
+            Record::Info { pid, status } => {
+                first_pid.get_or_insert(pid);
+                if let Some(status) = &status && first_pid == Some(pid) && root_exit.is_none() {
+                    root_exit = Some(status.shell_code());
+                }
+                let wanted = if opts.filter.pids.is_empty() {
+                    first_pid == Some(pid)
+                } else {
+                    opts.filter.keep_pid(pid)
+                };
+                if let Some(status) = status && wanted && exit.is_none() {
+                    exit = Some(ExitCode { pid, exitcode: status.shell_code() });
+                }
+            }
+            Record::Signal { pid, .. } => {
+                first_pid.get_or_insert(pid);
+            }
+            Record::Call { call, files } => {
+                first_pid.get_or_insert(call.pid);
+                if !opts.filter.keep_pid(call.pid) || !opts.filter.keep_syscall(&call.syscall) {
+                    continue;
+                }
+                stats.calls += 1;
+                pids.insert(call.pid);
+                *stats.syscalls.entry(call.syscall.clone()).or_default() += 1;
+                let Some(files) = files else {
+                    stats.unclassified_calls += 1;
+                    unknown.add(&call);
+                    continue;
                 };
-                if keep {
-                    records.push(f);
+                for e in AccessEvent::from_call(&call, files) {
+                    if !opts.filter.keep(&e) {
+                        continue;
+                    }
+                    stats.accesses += 1;
+                    let bytes = e.bytes.unwrap_or(0);
+                    match e.access {
+                        Access::Read => {
+                            stats.reads += 1;
+                            stats.bytes_read += bytes;
+                        }
+                        Access::Write => {
+                            stats.writes += 1;
+                            stats.bytes_written += bytes;
+                        }
+                    }
+                    if opts.command == Command::Events {
+                        write_event(&mut out, opts.format, &e)?;
+                    }
+                    let exec = parser.context().process_tree().get(e.pid).and_then(|p| p.exec.as_ref());
+                    sets.add_for_command(e.pid, exec, e.to_rwfile());
+                    if keep_events {
+                        events.push(e);
+                    }
                 }
             }
         }
     }
+    stats.pids = pids.len();
+    stats.suppressed = sets.suppressed();
 
-    let mut all_records: Vec<rwFile> = Vec::new();
-
-    for record in records {
-        match record {
-            rwFile::rfile(rf) => {
-                for r in rf.closure() {
-                    all_records.push(rwFile::rfile(r));
+    let tree = parser.context().process_tree();
+    // With --subtree every pid in the tree gets a line, even one that
+    // touched nothing itself.
+    let sets = move || {
+        if !opts.subtree {
+            return RwSets::from(sets);
+        }
+        let mut pids: BTreeSet<i32> = sets.pids().into_iter().collect();
+        pids.extend(tree.iter().map(|p| p.pid).filter(|&p| opts.filter.keep_pid(p)));
+        let subtrees = pids.into_iter().map(|pid| (pid, sets.subtree_sets(tree, pid))).collect();
+        RwSets { pids: subtrees, ..RwSets::from(sets) }
+    };
+    match (opts.command, opts.format) {
+        (Command::Events, Format::Text) => {}
+        (Command::Events, Format::Jsonl) => output::write_jsonl(&mut out, "rwset", &sets())?,
+        (Command::Events, Format::Json) => output::write_json(&mut out, &events, &sets())?,
+        (Command::RwSet, Format::Text) if opts.by_command => {
+            for run in &sets().commands {
+                let command = run.exec.as_ref().map_or("?".to_string(), |e| e.command_line());
+                for f in &run.sets.read {
+                    writeln!(out, "{}\t{command}\tread\t{f}", run.pid)?;
                 }
-            },
-            rwFile::wfile(wf) => {
-                for r in wf.closure() {
-                    all_records.push(rwFile::wfile(r));
+                for f in &run.sets.write {
+                    writeln!(out, "{}\t{command}\twrite\t{f}", run.pid)?;
                 }
-            },
+            }
         }
-    }
-
-    for record in all_records {
-        match record {
-            rwFile::rfile(rf) => {
-                if rf.fname != "/dev/tty" {
-                    read_set.insert(rf.fname);
+        (Command::RwSet, Format::Text) if opts.by_pid => {
+            for (pid, sets) in &sets().pids {
+                for f in &sets.read {
+                    writeln!(out, "{pid}\tread\t{f}")?;
                 }
-            },
-            rwFile::wfile(wf) => {
-                if wf.fname != "/dev/tty" {
-                    write_set.insert(wf.fname);
+                for f in &sets.write {
+                    writeln!(out, "{pid}\twrite\t{f}")?;
                 }
-            },
+            }
         }
-    };
-
-    return (read_set, write_set)
-}
-fn process_file(fname: &str, ctx: &mut Context) -> std::io::Result<()> {
-    let file = File::open(fname)?;
-    let reader = io::BufReader::new(file);
-
-    for (i, line_result) in reader.lines().enumerate() {
-        let line = line_result?; // handle possible I/O error
-        if let parse_line_ret::files(record) = parse_line(&line, ctx){
-            println!("{:?}", record); 
+        (Command::RwSet, Format::Text) => {
+            let sets = sets();
+            for f in &sets.read {
+                writeln!(out, "read\t{f}")?;
+            }
+            for f in &sets.write {
+                writeln!(out, "write\t{f}")?;
+            }
+        }
+        (Command::RwSet, format) => write_single(&mut out, format, "rwset", &sets())?,
+        (Command::ExitCode, format) => {
+            let Some(exit) = exit else {
+                return Err("no exit status in trace".into());
+            };
+            match format {
+                Format::Text => writeln!(out, "{}", exit.exitcode)?,
+                _ => write_single(&mut out, format, "exitcode", &exit)?,
+            }
+        }
+        (Command::Stats, Format::Text) => {
+            writeln!(out, "lines\t{}", stats.lines)?;
+            writeln!(out, "calls\t{}", stats.calls)?;
+            writeln!(out, "unclassified_calls\t{}", stats.unclassified_calls)?;
+            writeln!(out, "accesses\t{}", stats.accesses)?;
+            writeln!(out, "reads\t{}", stats.reads)?;
+            writeln!(out, "writes\t{}", stats.writes)?;
+            writeln!(out, "bytes_read\t{}", stats.bytes_read)?;
+            writeln!(out, "bytes_written\t{}", stats.bytes_written)?;
+            writeln!(out, "errors\t{}", stats.errors)?;
+            writeln!(out, "pids\t{}", stats.pids)?;
+            for (name, n) in &stats.syscalls {
+                writeln!(out, "syscall.{name}\t{n}")?;
+            }
+            for s in &stats.suppressed {
+                writeln!(out, "suppressed.{}\t{}", s.rule, s.count)?;
+            }
+        }
+        (Command::Stats, format) => write_single(&mut out, format, "stats", &stats)?,
+        (Command::UnknownSyscalls, Format::Text) => {
+            for (name, u) in unknown.by_count() {
+                writeln!(out, "{}\t{name}\t{}\t{}", u.count, u.first_line, u.example)?;
+            }
+        }
+        (Command::UnknownSyscalls, format) => {
+            write_single(&mut out, format, "unknown-syscalls", &Unknown { syscalls: &unknown })?
+        }
+        (Command::Tree, Format::Text) => {
+            let roots = if opts.filter.pids.is_empty() { tree.roots() } else { opts.filter.pids.clone() };
+            for pid in roots {
+                write_tree(&mut out, tree, pid, 0)?;
+            }
+        }
+        (Command::Tree, format) => {
+            let mut procs: Vec<&Process> = tree.iter().filter(|p| opts.filter.keep_pid(p.pid)).collect();
+            procs.sort_unstable_by_key(|p| p.pid);
+            let processes = procs.into_iter().map(|process| TreeEntry { process, running: process.is_running() }).collect();
+            write_single(&mut out, format, "tree", &Tree { processes })?
         }
     }
 
-    Ok(())
+    Ok(root_exit)
 }
+
 fn main() {
-    let mut ctx = Context::new();
-    ctx.set_dir(env::current_dir().unwrap().to_str().unwrap(), None);
     let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Usage: {} <filename>", args[0]);
-        std::process::exit(1);
+    let opts = match parse_args(&args[1..]) {
+        Ok(opts) => opts,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            eprintln!("{USAGE}");
+            process::exit(1);
+        }
+    };
+    let mut table = SyscallTable::new();
+    for path in &opts.tables {
+        match SyscallTable::load(Path::new(path)) {
+            Ok(t) => table.merge(t),
+            Err(e) => {
+                eprintln!("Error: {path}: {e}");
+                process::exit(1);
+            }
+        }
+    }
+    let mut rules = PathRules::none();
+    for rule in &opts.path_rules {
+        rules.push(rule.clone());
+    }
+    for path in &opts.rules_files {
+        match PathRules::load(Path::new(path)) {
+            Ok(r) => rules.extend(r),
+            Err(e) => {
+                eprintln!("Error: {path}: {e}");
+                process::exit(1);
+            }
+        }
+    }
+    if opts.default_excludes {
+        rules.extend(PathRules::defaults());
+    }
+    let mut parser = TraceParser::new().error_mode(opts.mode).syscall_table(table);
+    let mut errors = Vec::new();
+    let (result, exit) = match &opts.input {
+        Input::File(fname) => {
+            let result = file_records(fname)
+                .map_err(Into::into)
+                .and_then(|records| process(&opts, rules, &mut parser, records, &mut errors));
+            (result.map(|_| ()), None)
+        }
+        #[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+        Input::Run(program) => {
+            let mut cmd = process::Command::new(&program[0]);
+            cmd.args(&program[1..]);
+            match Tracer::spawn(&mut cmd) {
+                Ok(mut tracer) => {
+                    let result = process(&opts, rules, &mut parser, traced_records(&mut tracer), &mut errors);
+                    (result.map(|_| ()), tracer.root_status().map(|s| s.shell_code()))
+                }
+                Err(e) => (Err(format!("cannot run {}: {e}", program[0]).into()), None),
+            }
+        }
+        #[cfg(not(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+        Input::Run(_) => (Err("`run` needs Linux on x86_64 or aarch64".into()), None),
+        #[cfg(unix)]
+        Input::Trace { program, strace, string_limit } => {
+            let string_limit = string_limit.unwrap_or(DEFAULT_STRING_LIMIT);
+            match Strace::spawn(strace.as_ref(), program, string_limit) {
+                Ok(mut st) => {
+                    let result = process(&opts, rules, &mut parser, strace_records(&mut st), &mut errors);
+                    if result.is_err() {
+                        let _ = st.kill();
+                    }
+                    let status = st.wait();
+                    match (result, status) {
+                        (Ok(Some(code)), _) => (Ok(()), Some(code)),
+                        (Ok(None), Ok(status)) => (Ok(()), status.code().or(status.signal().map(|s| 128 + s))),
+                        (Ok(None), Err(e)) => (Err(e.into()), None),
+                        (Err(e), _) => (Err(e), None),
+                    }
+                }
+                Err(e) => (Err(format!("cannot run {strace}: {e}").into()), None),
+            }
+        }
+        #[cfg(not(unix))]
+        Input::Trace { .. } => (Err("`trace` needs a Unix host".into()), None),
     };
-    let fname = &args[1];
-    if let Err(e) = process_file(fname, &mut ctx) {
+    for d in &errors {
+        eprintln!("warning: {}", d);
+    }
+    if let Err(e) = result {
         eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+    if let Some(code) = exit {
+        process::exit(code);
     }
 }
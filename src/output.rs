@@ -0,0 +1,158 @@
+//! Machine-readable output.
+//!
+//! Every JSON object carries `"schema": SCHEMA_VERSION`; the version is bumped
+//! whenever a field is removed or changes meaning (adding fields is not a
+//! breaking change).
+//!
+//! In JSON Lines form each line is one object tagged with `"type"`:
+//!
+//! ```text
+//! {"schema":1,"type":"access","line":2,"pid":100,"syscall":"openat","path":"/home/u/foo","access":"read","intent":["read"],"ret":"3</home/u/foo>"}
+//! {"schema":1,"type":"rwset","read":["/home","/home/u","/home/u/foo"],"write":["/tmp","/tmp/out"],"bytes":{"/tmp/out":{"read":0,"written":12}}}
+//! ```
+//!
+//! The `access` objects come first, in trace order, followed by a single
+//! `rwset` object. In plain JSON form the same data is one document:
+//!
+//! ```text
+//! {"schema":1,"events":[{"line":2,...}],"read":[...],"write":[...]}
+//! ```
+//!
+//! `access` is `"read"` or `"write"`. Accesses made by an open also carry
+//! `intent`, the list of what its flags asked for: `read`, `write`,
+//! `create`, `excl`, `trunc`, `append`, `directory`, `path` (an `O_PATH`
+//! open, which only holds the path). An `O_RDWR` open is reported once as
+//! a read and once as a write. Data calls (`read`, `write`, `sendfile`,
+//! ...) carry `bytes`, and `rwset` sums them per file under `bytes`
+//! (omitted when there are none). The `read`/`write` sets are
+//! sorted and have the same contents as [`crate::parse_and_gather_cmd_rw_sets`].
+//! With `--by-pid` or `--subtree`, `rwset` also has `pids`, mapping each pid
+//! to its own `read`/`write` sets or to those of its whole process subtree.
+//! With `--by-command` it has `commands`, one entry per program run in
+//! trace order: `{"pid":..,"exec":{"exe":..,"argv":[..]},"read":[..],"write":[..]}`
+//! (`exec` is `null` if the trace never showed what the pid runs).
+//!
+//! Other CLI commands emit a single object in either form, with `"type"` set
+//! to `rwset`, `exitcode` (`{"pid":..,"exitcode":..}`, the shell-style code:
+//! 128 plus the signal number for a killed pid), `stats`,
+//! `unknown-syscalls`
+//! (`{"syscalls":{"name":{"count":..,"first_line":..,"example":".."},..}}`)
+//! or `tree` (`{"processes":[..]}`, one entry per pid in ascending order:
+//! `{"pid":..,"parent":..,"tgid":..,"children":[..],"exec":..,"execs":[..],
+//! "exit":..,"signals":[..],"running":..}`). `exit` is `null` for pids still
+//! running when the trace ended, and otherwise has `exitcode` (-1 if killed)
+//! and, for killed pids, `signal` and `core_dumped` when they apply.
+//! `signals` lists what was delivered to the pid, in order:
+//! `{"signal":"SIGPIPE","code":"SI_USER","sender_pid":..,"sender_uid":..}`,
+//! or `{"signal":"SIGSEGV","code":"SEGV_MAPERR","addr":"0x10"}` for faults;
+//! fields the trace did not show are omitted.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::event::AccessEvent;
+use crate::rwset::{ByteCount, CommandSets, PidSets, RwSetBuilder};
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Final read and write sets, sorted for stable output.
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct RwSets {
+    pub read: BTreeSet<String>,
+    pub write: BTreeSet<String>,
+    /// Bytes read and written per file, for files touched by data calls.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub bytes: BTreeMap<String, ByteCount>,
+    /// Sets of each pid, when asked for.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub pids: BTreeMap<i32, PidSets>,
+    /// Sets of each command run, when asked for.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub commands: Vec<CommandSets>,
+}
+
+impl From<(HashSet<String>, HashSet<String>)> for RwSets {
+    fn from((read, write): (HashSet<String>, HashSet<String>)) -> Self {
+        RwSets {
+            read: read.into_iter().collect(),
+            write: write.into_iter().collect(),
+            bytes: BTreeMap::new(),
+            pids: BTreeMap::new(),
+            commands: Vec::new(),
+        }
+    }
+}
+
+impl From<RwSetBuilder> for RwSets {
+    fn from(builder: RwSetBuilder) -> Self {
+        let bytes = builder.byte_counts().iter().map(|(k, v)| (k.clone(), *v)).collect();
+        let pids = builder
+            .pids()
+            .into_iter()
+            .filter_map(|pid| Some((pid, builder.pid_sets(pid)?.clone())))
+            .collect();
+        let commands = builder.commands().to_vec();
+        RwSets { bytes, pids, commands, ..RwSets::from(builder.into_sets()) }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+    Jsonl,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "jsonl" => Ok(Format::Jsonl),
+            _ => Err(format!("unknown format `{s}` (expected text, json or jsonl)")),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Tagged<'a, T> {
+    schema: u32,
+    #[serde(rename = "type")]
+    kind: &'a str,
+    #[serde(flatten)]
+    body: &'a T,
+}
+
+#[derive(Serialize)]
+struct Document<'a> {
+    schema: u32,
+    events: &'a [AccessEvent],
+    #[serde(flatten)]
+    sets: &'a RwSets,
+}
+
+/// Write one JSON Lines record.
+pub fn write_jsonl<W: Write, T: Serialize>(out: &mut W, kind: &str, body: &T) -> io::Result<()> {
+    let tagged = Tagged { schema: SCHEMA_VERSION, kind, body };
+    serde_json::to_writer(&mut *out, &tagged)?;
+    writeln!(out)
+}
+
+/// Write one record as a pretty-printed JSON document, tagged the same way
+/// as [`write_jsonl`].
+pub fn write_json_tagged<W: Write, T: Serialize>(out: &mut W, kind: &str, body: &T) -> io::Result<()> {
+    let tagged = Tagged { schema: SCHEMA_VERSION, kind, body };
+    serde_json::to_writer_pretty(&mut *out, &tagged)?;
+    writeln!(out)
+}
+
+/// Write events and sets as a single JSON document.
+pub fn write_json<W: Write>(out: &mut W, events: &[AccessEvent], sets: &RwSets) -> io::Result<()> {
+    let doc = Document { schema: SCHEMA_VERSION, events, sets };
+    serde_json::to_writer_pretty(&mut *out, &doc)?;
+    writeln!(out)
+}
@@ -0,0 +1,1424 @@
+use phf::phf_set;
+use serde::Serialize;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+use crate::args::{parse_arg, parse_args, Arg, FdNum};
+use crate::context::Context;
+use crate::error::{ParseError, ParseErrorKind};
+use crate::files::{RwFile, RFile, WFile};
+use crate::flags::{AtFlags, CloneFlags, MapFlags, OpenFlags, OpenIntent, ProtFlags, RenameFlags, ResolveFlags};
+use crate::lexer::{split_call, split_pid};
+use crate::table::{Layout, Rule, Semantics};
+
+pub static R_FIRST_PATH_SET: phf::Set<&'static str> = phf_set! {
+    "execve", "stat", "lstat", "access", "statfs",
+    "readlink", "getxattr", "lgetxattr", "llistxattr",
+    "listxattr", "stat64", "lstat64", "oldstat", "oldlstat", "statfs64",
+    "chroot", "uselib",
+};
+
+pub static W_FIRST_PATH_SET: phf::Set<&'static str> = phf_set! {
+    "mkdir", "rmdir", "truncate", "creat", "chmod", "chown",
+    "lchown", "utime", "mknod", "utimes", "acct", "unlink",
+    "setxattr", "removexattr",
+    "lsetxattr", "lremovexattr", "truncate64", "chown32", "lchown32",
+    "swapon", "swapoff", "umount", "umount2",
+};
+
+pub static R_FD_PATH_SET: phf::Set<&'static str> = phf_set! {
+    "fstatat", "newfstatat", "statx", "name_to_handle_at",
+    "readlinkat", "faccessat", "execveat", "faccessat2",
+    "fstatat64", "getxattrat", "listxattrat", "fspick",
+};
+
+pub static W_FD_PATH_SET: phf::Set<&'static str> = phf_set! {
+    "unlinkat", "utimensat", "mkdirat", "mknodat", "fchownat", "futimesat",
+    "fchmodat", "fchmodat2", "setxattrat", "removexattrat", "mount_setattr",
+};
+
+/// Calls on an fd alone that look at the file behind it.
+pub static R_FD_SET: phf::Set<&'static str> = phf_set! {
+    "fstat", "fstat64", "fstatfs", "fstatfs64", "fgetxattr", "flistxattr",
+    "getdents", "getdents64", "readahead", "quotactl_fd",
+};
+
+/// Calls on an fd alone that change the file behind it.
+pub static W_FD_SET: phf::Set<&'static str> = phf_set! {
+    "fchmod", "fchown", "fchown32", "fsetxattr", "fremovexattr",
+    "ftruncate", "ftruncate64", "fallocate",
+};
+
+/// Calls that never touch the filesystem.
+pub static IGNORE_SET: phf::Set<&'static str> = phf_set! {
+    "getpid", "getcwd",
+    "getppid", "gettid", "getuid", "geteuid", "getgid", "getegid", "getgroups",
+    "getresuid", "getresgid", "getpgrp", "getpgid", "getsid", "setuid", "setgid",
+    "setreuid", "setregid", "setresuid", "setresgid", "setgroups", "setpgid",
+    "setsid", "setfsuid", "setfsgid",
+    "brk", "munmap", "mprotect", "mremap", "madvise", "mlock", "mlock2",
+    "munlock", "mlockall", "munlockall", "mincore", "msync", "membarrier",
+    "rt_sigaction", "rt_sigprocmask", "rt_sigreturn", "rt_sigsuspend",
+    "rt_sigpending", "rt_sigtimedwait", "rt_sigqueueinfo", "sigaltstack",
+    "kill", "tgkill", "tkill", "pause", "alarm",
+    "nanosleep", "clock_nanosleep", "clock_gettime", "clock_getres",
+    "gettimeofday", "time", "times", "getitimer", "setitimer", "timer_create",
+    "timer_settime", "timer_gettime", "timer_delete", "timerfd_create",
+    "timerfd_settime", "timerfd_gettime",
+    "sched_yield", "sched_getaffinity", "sched_setaffinity", "sched_getparam",
+    "sched_setparam", "sched_getscheduler", "sched_setscheduler",
+    "sched_get_priority_max", "sched_get_priority_min", "getpriority",
+    "setpriority",
+    "futex", "set_robust_list", "get_robust_list", "set_tid_address",
+    "arch_prctl", "prctl", "rseq", "uname", "sysinfo", "getrlimit", "setrlimit",
+    "prlimit64", "getrusage", "umask", "capget", "capset", "personality",
+    "getrandom", "wait4", "waitid", "exit", "exit_group",
+    "ioctl", "lseek", "_llseek", "fsync", "fdatasync", "sync", "syncfs",
+    "sync_file_range", "flock", "fadvise64", "poll", "ppoll", "select",
+    "pselect6", "epoll_create", "epoll_create1", "epoll_ctl", "epoll_wait",
+    "epoll_pwait", "epoll_pwait2", "eventfd", "eventfd2", "signalfd",
+    "signalfd4", "inotify_init", "inotify_init1", "inotify_rm_watch",
+    "fanotify_init", "memfd_create", "userfaultfd", "pidfd_open",
+    "pidfd_send_signal", "pidfd_getfd", "vmsplice",
+    "listen", "accept", "accept4", "sendto", "recvfrom", "sendmsg", "recvmsg",
+    "sendmmsg", "recvmmsg", "shutdown", "getsockname", "getpeername",
+    "socketpair", "setsockopt", "getsockopt",
+    // Queue names live in their own namespace, not under any directory.
+    "mq_open", "mq_unlink", "mq_timedsend", "mq_timedreceive", "mq_notify",
+    "mq_getsetattr",
+    // Mount setup that names no path; `fsconfig` and `move_mount` do.
+    "fsopen", "fsmount",
+};
+
+type Result<T> = std::result::Result<T, ParseErrorKind>;
+
+/// Everything after the first argument; an empty list has no first argument.
+fn skip_first<'b, 'a>(args: &'b [Arg<'a>]) -> Result<&'b [Arg<'a>]> {
+    args.get(1..).ok_or(ParseErrorKind::MissingArgument(0))
+}
+
+fn nth_arg<'b, 'a>(args: &'b [Arg<'a>], i: usize) -> Result<&'b Arg<'a>> {
+    args.get(i).ok_or(ParseErrorKind::MissingArgument(i))
+}
+
+
+/// How a process ended, from its `+++` line.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExitStatus {
+    /// Exit code, or -1 if it was killed.
+    pub exitcode: i32,
+    /// The signal that killed it, e.g. `SIGSEGV`, if the trace named one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signal: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub core_dumped: bool,
+}
+
+impl ExitStatus {
+    pub fn exited(exitcode: i32) -> ExitStatus {
+        ExitStatus { exitcode, signal: None, core_dumped: false }
+    }
+
+    pub fn killed(signal: Option<String>, core_dumped: bool) -> ExitStatus {
+        ExitStatus { exitcode: -1, signal, core_dumped }
+    }
+
+    /// The status a shell would report: the exit code, or 128 plus the
+    /// signal number if it was killed (1 if the trace did not say by what).
+    pub fn shell_code(&self) -> i32 {
+        match &self.signal {
+            None if self.exitcode >= 0 => self.exitcode,
+            Some(sig) => signal_number(sig).map_or(1, |n| 128 + n),
+            None => 1,
+        }
+    }
+}
+
+/// Linux signal numbers, which x86_64 and aarch64 share.
+const SIGNAL_NUMBERS: &[(&str, i32)] = &[
+    ("SIGHUP", 1),
+    ("SIGINT", 2),
+    ("SIGQUIT", 3),
+    ("SIGILL", 4),
+    ("SIGTRAP", 5),
+    ("SIGABRT", 6),
+    ("SIGBUS", 7),
+    ("SIGFPE", 8),
+    ("SIGKILL", 9),
+    ("SIGUSR1", 10),
+    ("SIGSEGV", 11),
+    ("SIGUSR2", 12),
+    ("SIGPIPE", 13),
+    ("SIGALRM", 14),
+    ("SIGTERM", 15),
+    ("SIGSTKFLT", 16),
+    ("SIGCHLD", 17),
+    ("SIGCONT", 18),
+    ("SIGSTOP", 19),
+    ("SIGTSTP", 20),
+    ("SIGTTIN", 21),
+    ("SIGTTOU", 22),
+    ("SIGURG", 23),
+    ("SIGXCPU", 24),
+    ("SIGXFSZ", 25),
+    ("SIGVTALRM", 26),
+    ("SIGPROF", 27),
+    ("SIGWINCH", 28),
+    ("SIGIO", 29),
+    ("SIGPWR", 30),
+    ("SIGSYS", 31),
+];
+
+/// The number of a signal as strace names it: `SIGSEGV`, `SIGRT_2` (counted
+/// from the kernel's `SIGRTMIN`, 32) or `SIG34`.
+fn signal_number(name: &str) -> Option<i32> {
+    if let Some(&(_, n)) = SIGNAL_NUMBERS.iter().find(|&&(s, _)| s == name) {
+        return Some(n)
+    }
+    if let Some(n) = name.strip_prefix("SIGRT_") {
+        return n.parse::<i32>().ok().map(|n| 32 + n)
+    }
+    name.strip_prefix("SIG")?.parse().ok()
+}
+
+/// strace's wording: `exited with 0`, `killed by SIGSEGV (core dumped)`.
+impl fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.signal {
+            None if self.exitcode >= 0 => return write!(f, "exited with {}", self.exitcode),
+            None => f.write_str("killed")?,
+            Some(sig) => write!(f, "killed by {sig}")?,
+        }
+        if self.core_dumped {
+            f.write_str(" (core dumped)")?;
+        }
+        Ok(())
+    }
+}
+
+/// The pid in `+++ superseded by execve in pid N +++`: thread `N` of the
+/// line's thread group called `execve`, which made it the group leader.
+fn parse_superseded(l: &str) -> Option<i32> {
+    let body = l.trim_start_matches("+++").trim_end_matches("+++").trim();
+    body.strip_prefix("superseded by execve in pid ")?.trim().parse().ok()
+}
+
+fn parse_info(l: &str) -> Result<ExitStatus> {
+    let body = l.trim_start_matches("+++").trim_end_matches("+++").trim();
+    if let Some(code) = body.strip_prefix("exited with ") {
+        let exitcode = code.trim().parse::<i32>()
+            .map_err(|_| ParseErrorKind::BadExitStatus(code.trim().to_string()))?;
+        Ok(ExitStatus::exited(exitcode))
+    } else if let Some(rest) = body.strip_prefix("killed by ") {
+        let signal = rest.split_whitespace().next().map(str::to_string);
+        Ok(ExitStatus::killed(signal, rest.contains("(core dumped)")))
+    } else if l.contains("Killed") || l.contains("killed") {
+        Ok(ExitStatus::killed(None, false))
+    } else {
+        Err(ParseErrorKind::UnhandledInfo)
+    }
+}
+
+/// A signal delivered to a traced process, from a
+/// `--- SIGPIPE {si_signo=SIGPIPE, si_code=SI_USER, si_pid=101, ...} ---` line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Signal {
+    /// e.g. `SIGPIPE`.
+    pub signal: String,
+    /// `si_code` as strace names it (`SI_USER`, `SEGV_MAPERR`, ...).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    /// Who sent it, for signals from `kill` and friends and for `SIGCHLD`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sender_pid: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sender_uid: Option<u32>,
+    /// Faulting address of `SIGSEGV`, `SIGBUS` and the like, e.g. `0x10`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub addr: Option<String>,
+}
+
+/// `SIGPIPE from 101`, `SIGSEGV at 0x10`.
+impl fmt::Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.signal)?;
+        if let Some(pid) = self.sender_pid {
+            write!(f, " from {pid}")?;
+        }
+        if let Some(addr) = &self.addr {
+            write!(f, " at {addr}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The signal a `--- SIGNAME {siginfo} ---` line reports; `None` for other
+/// `---` lines such as `--- stopped by SIGSTOP ---`.
+pub fn parse_signal(l: &str) -> Option<Signal> {
+    let body = l.trim().strip_prefix("---")?.strip_suffix("---")?.trim();
+    let (name, info) = body.split_once(' ')?;
+    if !name.starts_with("SIG") {
+        return None;
+    }
+    let info = parse_arg(info);
+    if !matches!(info, Arg::Struct(_)) {
+        return None;
+    }
+    let int = |field| info.field(field).and_then(Arg::as_int);
+    let addr = info.field("si_addr").map(|a| match a {
+        Arg::Int(i) => format!("{i:#x}"),
+        a => a.to_string(),
+    });
+    Some(Signal {
+        signal: name.to_string(),
+        code: info.field("si_code").map(|a| a.to_string()),
+        sender_pid: int("si_pid").and_then(|p| i32::try_from(p).ok()),
+        sender_uid: int("si_uid").and_then(|u| u32::try_from(u).ok()),
+        addr,
+    })
+}
+
+fn parse_string<'b>(arg: &'b Arg) -> Result<&'b str> {
+    arg.as_str().ok_or_else(|| ParseErrorKind::BadString(arg.to_string()))
+}
+
+fn is_absolute_path(path: &str) -> bool{
+    !path.is_empty() && path.starts_with('/')
+}
+
+fn is_ret_err(ret: &str) -> bool{
+    ret.trim().starts_with('-')
+}
+
+/// A plain fd number (not `AT_FDCWD`, not an error return).
+fn fd_num(arg: &Arg) -> Option<i32> {
+    match arg.fd()? {
+        FdNum::Num(n) if n >= 0 => Some(n),
+        _ => None,
+    }
+}
+
+/// The path open on an fd: the `<path>` annotation strace's `-y` adds, or
+/// else what the fd table recorded for it.
+fn fd_annotation(pid: i32, arg: &Arg, ctx: &Context) -> Result<String> {
+    arg.fd_path()
+        .or_else(|| ctx.get_fd(pid, fd_num(arg)?))
+        .map(str::to_string)
+        .ok_or_else(|| ParseErrorKind::MissingFdPath(arg.to_string()))
+}
+
+/// Path of the fd an open returned; without `-y` it is the path that was opened.
+fn get_ret_file_path(ret: &Arg, opened: &str) -> String {
+    ret.fd_path().unwrap_or(opened).to_string()
+}
+
+fn convert_absolute(cur_dir: &Path, path: &str) -> PathBuf {
+    let p = Path::new(path);
+    if is_absolute_path(path){
+        p.components().collect()
+    } else {
+        cur_dir.join(p).components().collect()
+    }
+
+}
+
+fn path_str(p: &Path) -> String {
+    p.to_string_lossy().into_owned()
+}
+
+fn get_path_first_path(pid: i32, args: &[Arg], ctx: &mut Context) -> Result<PathBuf> {
+    let a = parse_string(nth_arg(args, 0)?)?;
+
+    let dir = ctx.get_dir(pid);
+    let abs_path = convert_absolute(Path::new(&dir), a);
+
+    if abs_path.as_os_str().is_empty() {
+        Err(ParseErrorKind::MissingArgument(0))
+    } else {
+        Ok(abs_path)
+    }
+}
+
+
+fn parse_r_first_path(pid: i32, args: &[Arg], _ret: &str, ctx: &mut Context) -> Result<RwFile> {
+    Ok(RwFile::Read(RFile::new(&path_str(&get_path_first_path(pid, args, ctx)?))))
+}
+
+fn parse_w_first_path(pid: i32, args: &[Arg], ret: &str, ctx: &mut Context) -> Result<RwFile> {
+    let path = path_str(&get_path_first_path(pid, args, ctx)?);
+    if is_ret_err(ret) {
+        Ok(RwFile::Read(RFile::new(&path)))
+    }
+    else {
+        Ok(RwFile::Write(WFile::new(&path)))
+    }
+}
+
+enum ArgPos {
+    Single(usize),
+    Multiple(Vec<usize>),
+}
+
+/// Resolves the path arguments at `positions` against the cwd of `pid`.
+fn get_path_at(pid: i32, positions: ArgPos, args: &[Arg], ctx: &mut Context) -> Result<Vec<PathBuf>>{
+    match positions {
+        ArgPos::Single(i) =>{
+            let m = nth_arg(args, i)?;
+            Ok(vec![convert_absolute(Path::new(& ctx.get_dir(pid)), parse_string(m)?)])
+        }
+        ArgPos::Multiple(l) => {
+            let mut res: Vec<PathBuf> = Vec::new();
+            for i in l {
+                res.push(convert_absolute(Path::new(& ctx.get_dir(pid)), parse_string(nth_arg(args, i)?)?));
+            }
+            Ok(res)
+        }
+    }
+}
+
+fn parse_rename(pid: i32, args: &[Arg], _ret: &str, ctx: &mut Context) -> Result<Vec<RwFile>> {
+    let paths = get_path_at(pid, ArgPos::Multiple(vec![0,1]), args, ctx)?;
+    Ok(vec![
+        RwFile::Write(WFile::new(&path_str(&paths[0]))),
+        RwFile::Write(WFile::new(&path_str(&paths[1]))),
+        ])
+}
+
+fn parse_link(pid: i32, args: &[Arg], _ret: &str, ctx: &mut Context) -> Result<Vec<RwFile>> {
+    let paths = get_path_at(pid, ArgPos::Multiple(vec![0,1]), args, ctx)?;
+    Ok(vec![
+        RwFile::Read(RFile::new(&path_str(&paths[0]))),
+        RwFile::Write(WFile::new(&path_str(&paths[1]))),
+        ])
+
+}
+
+fn parse_chdir(pid: i32, args: &[Arg], ret: &str, ctx: &mut Context) -> Result<RwFile> {
+    let new_path = path_str(&get_path_at(pid, ArgPos::Single(0), args, ctx)?[0]);
+    if !is_ret_err(ret) {
+        ctx.set_dir(&new_path, Some(pid));
+    }
+
+    Ok(RwFile::Read(RFile::new(&new_path)))
+
+}
+
+/// Flags we cannot decode are taken as a plain write.
+fn handle_open_flag(flags: &Arg) -> OpenIntent {
+    OpenFlags::from_arg(flags)
+        .unwrap_or(OpenFlags::O_WRONLY)
+        .intent()
+}
+
+/// Classify an open and record the fd it returned. An `O_RDWR` open is both
+/// a read and a write; an `O_PATH` open is neither.
+fn handle_open_common(pid: i32, total_path: PathBuf, flags: &Arg, ret :&str, ctx: &mut Context) -> Result<Vec<RwFile>>{
+    let file_path = path_str(&total_path);
+    let intent = handle_open_flag(flags);
+    if is_ret_err(ret){
+        if intent.path {
+            return Ok(vec![])
+        }
+        return Ok(vec![RwFile::Read(RFile::new(&file_path).with_intent(intent))])
+    }
+    let ret = parse_arg(ret);
+    let ret_path = get_ret_file_path(&ret, &file_path);
+    if let Some(fd) = fd_num(&ret) {
+        ctx.set_fd(pid, fd, &ret_path);
+    }
+    // The returned fd's `-y` path is only worth a second access when it
+    // differs, e.g. because the argument went through a symlink.
+    let mut paths = vec![&file_path];
+    if ret_path != file_path {
+        paths.push(&ret_path);
+    }
+    let mut files = Vec::new();
+    for path in paths {
+        if intent.reads() {
+            files.push(RwFile::Read(RFile::new(path).with_intent(intent)));
+        }
+        if intent.modifies() {
+            files.push(RwFile::Write(WFile::new(path).with_intent(intent)));
+        }
+    }
+    Ok(files)
+}
+
+fn parse_openat(pid: i32, args: &[Arg], ret :&str, ctx: &mut Context) -> Result<Vec<RwFile>>{
+    let path = parse_string(nth_arg(args, 1)?)?;
+    let dfd = nth_arg(args, 0)?;
+    let flags = nth_arg(args, 2)?;
+    if path.is_empty(){
+        return Ok(vec![])
+    };
+    let Some(total_path) = resolve_at(pid, dfd, path, ctx)? else {
+        return Ok(vec![])
+    };
+    handle_open_common(pid, total_path, flags, ret, ctx)
+}
+
+/// `openat2(dirfd, path, {flags=..., mode=..., resolve=...}, size)`. With
+/// `RESOLVE_IN_ROOT` or `RESOLVE_BENEATH` the path is confined to `dirfd`.
+fn parse_openat2(pid: i32, args: &[Arg], ret: &str, ctx: &mut Context) -> Result<Vec<RwFile>> {
+    let path = parse_string(nth_arg(args, 1)?)?;
+    let dfd = nth_arg(args, 0)?;
+    let how = nth_arg(args, 2)?;
+    let flags = how.field("flags").unwrap_or(&Arg::Int(0));
+    let resolve = how.field("resolve").and_then(ResolveFlags::from_arg).unwrap_or_default();
+    if path.is_empty() {
+        return Ok(vec![])
+    }
+    let confined = resolve.contains(ResolveFlags::RESOLVE_IN_ROOT) || resolve.contains(ResolveFlags::RESOLVE_BENEATH);
+    let total_path = if confined {
+        let dir = at_dir(pid, dfd, ctx)?;
+        is_absolute_path(&dir).then(|| resolve_beneath(&dir, path))
+    } else {
+        resolve_at(pid, dfd, path, ctx)?
+    };
+    let Some(total_path) = total_path else {
+        return Ok(vec![])
+    };
+    handle_open_common(pid, total_path, flags, ret, ctx)
+}
+
+fn parse_open(pid:i32, args: &[Arg], ret :&str, ctx: &mut Context) -> Result<Vec<RwFile>>{
+    let total_path = get_path_first_path(pid, args, ctx)?;
+
+    let flags = nth_arg(args, 1)?;
+
+    handle_open_common(pid, total_path, flags, ret, ctx)
+
+}
+
+/// `creat(path, mode)` is `open(path, O_WRONLY|O_CREAT|O_TRUNC, mode)`.
+fn parse_creat(pid: i32, args: &[Arg], ret: &str, ctx: &mut Context) -> Result<Vec<RwFile>> {
+    let total_path = get_path_first_path(pid, args, ctx)?;
+    let flags = Arg::Flags(vec!["O_WRONLY", "O_CREAT", "O_TRUNC"]);
+    handle_open_common(pid, total_path, &flags, ret, ctx)
+}
+
+fn at_dir(pid: i32, dirfd: &Arg, ctx: &mut Context) -> Result<String> {
+    match (dirfd.fd(), dirfd.fd_path()) {
+        (Some(FdNum::AtFdcwd), None) => Ok(ctx.get_dir(pid)),
+        _ => fd_annotation(pid, dirfd, ctx),
+    }
+}
+
+/// `path` under `root` with `RESOLVE_IN_ROOT`/`RESOLVE_BENEATH` semantics:
+/// a leading `/` means `root`, and `..` never climbs above it.
+fn resolve_beneath(root: &str, path: &str) -> PathBuf {
+    let root = Path::new(root);
+    let mut res = root.to_path_buf();
+    for c in Path::new(path).components() {
+        match c {
+            Component::Normal(p) => res.push(p),
+            Component::ParentDir => {
+                if res != root {
+                    res.pop();
+                }
+            }
+            Component::RootDir => res = root.to_path_buf(),
+            Component::CurDir | Component::Prefix(_) => {}
+        }
+    }
+    res
+}
+
+/// Resolve `path` relative to `dirfd` the way the `*at` calls do: absolute
+/// paths stand alone, `AT_FDCWD` is the pid's working directory, and any
+/// other fd is the directory it has open. An empty path (`AT_EMPTY_PATH`,
+/// or `readlinkat`) names the fd itself. `None` when the fd is a pipe or
+/// socket rather than a file, as for [`data_fd_path`].
+fn resolve_at(pid: i32, dirfd: &Arg, path: &str, ctx: &mut Context) -> Result<Option<PathBuf>> {
+    if is_absolute_path(path) {
+        return Ok(Some(Path::new(path).components().collect()))
+    }
+    let dir = at_dir(pid, dirfd, ctx)?;
+    if !is_absolute_path(&dir) {
+        return Ok(None)
+    }
+    Ok(Some(Path::new(&dir).join(path).components().collect()))
+}
+
+fn get_path_from_fd_path(pid: i32, args: &[Arg], ctx: &mut Context) -> Result<Option<PathBuf>>{
+    let a0 = nth_arg(args, 0)?;
+    let a1 = parse_string(nth_arg(args, 1)?)?;
+    resolve_at(pid, a0, a1, ctx)
+}
+
+/// `AT_*` flags of a `*at` call that takes them, wherever its flags
+/// argument is; `None` for calls without one.
+fn at_flags(syscall: &str, args: &[Arg]) -> Option<AtFlags> {
+    let i = match syscall {
+        "statx" | "getxattrat" | "listxattrat" | "setxattrat" | "removexattrat" | "mount_setattr" => 2,
+        "fstatat" | "newfstatat" | "fstatat64" | "faccessat2" | "utimensat" | "fchmodat2" => 3,
+        "name_to_handle_at" | "fchownat" | "linkat" | "execveat" => 4,
+        _ => return None,
+    };
+    Some(args.get(i).and_then(AtFlags::from_arg).unwrap_or_default())
+}
+
+/// The file a `*at` call with `flags` names. An empty path is `dirfd`
+/// itself only with `AT_EMPTY_PATH` (or as `utimensat`'s `NULL`); without
+/// it the call fails with `ENOENT` and names nothing. Calls that take no
+/// flags (`readlinkat`, table rules) always mean the fd.
+///
+/// `AT_SYMLINK_NOFOLLOW` and `AT_SYMLINK_FOLLOW` do not change the result:
+/// the trace never shows where a link points, so the path as given is what
+/// goes into the sets either way.
+fn get_at_path(pid: i32, args: &[Arg], flags: Option<AtFlags>, ctx: &mut Context) -> Result<Option<PathBuf>> {
+    let path_arg = nth_arg(args, 1)?;
+    let path = parse_string(path_arg)?;
+    let names_fd = match flags {
+        None => true,
+        Some(f) => f.contains(AtFlags::AT_EMPTY_PATH) || *path_arg == Arg::Null,
+    };
+    if path.is_empty() && !names_fd {
+        return Ok(None)
+    }
+    resolve_at(pid, nth_arg(args, 0)?, path, ctx)
+}
+
+/// The two `(dirfd, path)` pairs of `renameat` and `move_mount`, leaving out
+/// any that do not name a file.
+fn fd_path_pair(pid: i32, args: &[Arg], ctx: &mut Context) -> Result<Vec<String>> {
+    let first = get_path_from_fd_path(pid, args, ctx)?;
+    let second = get_path_from_fd_path(pid, skip_first(skip_first(args)?)?, ctx)?;
+    Ok([first, second].iter().flatten().map(|p| path_str(p)).collect())
+}
+
+/// `renameat2` may also pass `RENAME_EXCHANGE`, which swaps the two paths so
+/// both are read as well as written. A failed rename changes nothing.
+fn parse_renameat(pid: i32, args: &[Arg], ret: &str, ctx: &mut Context) -> Result<Vec<RwFile>> {
+    let paths = fd_path_pair(pid, args, ctx)?;
+    let flags = args.get(4).and_then(RenameFlags::from_arg).unwrap_or_default();
+    if is_ret_err(ret) {
+        return Ok(paths.iter().map(|p| RwFile::Read(RFile::new(p))).collect());
+    }
+    let mut files: Vec<RwFile> = paths.iter().map(|p| RwFile::Write(WFile::new(p))).collect();
+    if flags.contains(RenameFlags::RENAME_EXCHANGE) {
+        files.extend(paths.iter().map(|p| RwFile::Read(RFile::new(p))));
+    }
+    Ok(files)
+}
+
+/// `linkat(olddirfd, oldpath, newdirfd, newpath, flags)`; with
+/// `AT_EMPTY_PATH` the file linked is the one open on `olddirfd`.
+fn parse_linkat(pid: i32, args: &[Arg], ret: &str, ctx: &mut Context) -> Result<Vec<RwFile>> {
+    let old = get_at_path(pid, args, at_flags("linkat", args), ctx)?;
+    let new = get_path_from_fd_path(pid, skip_first(skip_first(args)?)?, ctx)?;
+    let mut files: Vec<RwFile> = old.iter().map(|p| RwFile::Read(RFile::new(&path_str(p)))).collect();
+    let Some(new) = new.map(|p| path_str(&p)) else {
+        return Ok(files)
+    };
+    if is_ret_err(ret) {
+        files.push(RwFile::Read(RFile::new(&new)));
+    } else {
+        files.push(RwFile::Write(WFile::new(&new)));
+    }
+    Ok(files)
+}
+
+fn parse_r_fd_path(pid: i32, args: &[Arg], _ret :&str, flags: Option<AtFlags>, ctx: &mut Context) -> Result<Vec<RwFile>> {
+    let path = get_at_path(pid, args, flags, ctx)?;
+    Ok(path.iter().map(|p| RwFile::Read(RFile::new(&path_str(p)))).collect())
+}
+
+fn parse_w_fd_path(pid: i32, args: &[Arg], ret :&str, flags: Option<AtFlags>, ctx: &mut Context) -> Result<Vec<RwFile>> {
+    let path = get_at_path(pid, args, flags, ctx)?;
+    Ok(path
+        .iter()
+        .map(|p| {
+            let p = path_str(p);
+            if is_ret_err(ret) {
+                RwFile::Read(RFile::new(&p))
+            } else {
+                RwFile::Write(WFile::new(&p))
+            }
+        })
+        .collect())
+}
+
+/// The strings of an `argv`-style array; `NULL` is empty.
+fn string_list(arg: &Arg) -> Vec<String> {
+    match arg {
+        Arg::Array(items) => items
+            .iter()
+            .filter_map(|a| match a {
+                Arg::Str { value, .. } => Some(value.clone()),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// `execve(path, argv, envp)`: the program is read and, if the call
+/// succeeded, is what the process now runs.
+fn parse_execve(pid: i32, args: &[Arg], ret: &str, ctx: &mut Context) -> Result<Vec<RwFile>> {
+    let file = parse_r_first_path(pid, args, ret, ctx)?;
+    if !is_ret_err(ret) {
+        ctx.exec(pid, file.fname(), string_list(nth_arg(args, 1)?));
+    }
+    Ok(vec![file])
+}
+
+/// `execveat(dirfd, path, argv, envp, flags)`; with `AT_EMPTY_PATH` the
+/// program is `dirfd` itself.
+fn parse_execveat(pid: i32, args: &[Arg], ret: &str, ctx: &mut Context) -> Result<Vec<RwFile>> {
+    let files = parse_r_fd_path(pid, args, ret, at_flags("execveat", args), ctx)?;
+    if let Some(file) = files.first() && !is_ret_err(ret) {
+        ctx.exec(pid, file.fname(), string_list(nth_arg(args, 2)?));
+    }
+    Ok(files)
+}
+
+/// `clone(..., flags=..., ...)` or `clone3({flags=..., ...}, size)`.
+fn parse_clone(pid:i32, args: &[Arg], ret :&str, ctx: &mut Context) -> Result<()> {
+    let child = ret.trim().parse().unwrap_or(-1);
+    if child < 0 {
+        return Ok(())
+    }
+    let flags = args
+        .iter()
+        .find_map(|a| a.named("flags").or_else(|| a.field("flags")))
+        .ok_or(ParseErrorKind::MissingCloneFlags)?;
+
+    let flags = CloneFlags::from_arg(flags).unwrap_or_default();
+    ctx.clone_process(pid, child, flags);
+    ctx.clone_fds(pid, child, flags.contains(CloneFlags::CLONE_FILES));
+    Ok(())
+}
+
+/// `fork`/`vfork`: the child gets a copy of the fd table and of the
+/// working directory.
+fn parse_fork(pid: i32, ret: &str, ctx: &mut Context) {
+    if let Ok(child) = ret.trim().parse::<i32>()
+        && child > 0
+    {
+        ctx.clone_process(pid, child, CloneFlags::empty());
+        ctx.clone_fds(pid, child, false);
+    }
+}
+
+/// `unshare(flags)`: `CLONE_FS` and `CLONE_FILES` stop sharing the working
+/// directory and fd table.
+fn parse_unshare(pid: i32, args: &[Arg], ret: &str, ctx: &mut Context) -> Result<()> {
+    if is_ret_err(ret) {
+        return Ok(())
+    }
+    let flags = CloneFlags::from_arg(nth_arg(args, 0)?).unwrap_or_default();
+    if flags.contains(CloneFlags::CLONE_FS) {
+        ctx.unshare_fs(pid);
+    }
+    if flags.contains(CloneFlags::CLONE_FILES) {
+        ctx.unshare_fds(pid);
+    }
+    Ok(())
+}
+
+/// `dup(old)`, `dup2(old, new)`, `dup3(old, new, flags)` and
+/// `fcntl(old, F_DUPFD*, min)`: the new fd is the return value.
+fn parse_dup(pid: i32, args: &[Arg], ret: &str, ctx: &mut Context) -> Result<()> {
+    let old = nth_arg(args, 0)?;
+    let ret = parse_arg(ret);
+    let Some(new) = fd_num(&ret) else {
+        return Ok(())
+    };
+    match ret.fd_path().or(old.fd_path()) {
+        Some(path) => ctx.set_fd(pid, new, path),
+        None => match fd_num(old) {
+            Some(old) => ctx.dup_fd(pid, old, new),
+            None => ctx.close_fd(pid, new),
+        },
+    }
+    Ok(())
+}
+
+fn parse_fcntl(pid: i32, args: &[Arg], ret: &str, ctx: &mut Context) -> Result<()> {
+    let cmd = nth_arg(args, 1)?;
+    if cmd.has_flag("F_DUPFD") || cmd.has_flag("F_DUPFD_CLOEXEC") {
+        parse_dup(pid, args, ret, ctx)?;
+    }
+    Ok(())
+}
+
+fn parse_close(pid: i32, args: &[Arg], ctx: &mut Context) -> Result<()> {
+    if let Some(fd) = fd_num(nth_arg(args, 0)?) {
+        ctx.close_fd(pid, fd);
+    }
+    Ok(())
+}
+
+/// `close_range(first, last, flags)`; `CLOSE_RANGE_CLOEXEC` only marks the
+/// fds, `CLOSE_RANGE_UNSHARE` unshares the table first.
+fn parse_close_range(pid: i32, args: &[Arg], ret: &str, ctx: &mut Context) -> Result<()> {
+    if is_ret_err(ret) {
+        return Ok(())
+    }
+    let first = fd_num(nth_arg(args, 0)?).unwrap_or(0);
+    // `~0U` and friends mean "to the end".
+    let last = nth_arg(args, 1)?
+        .as_int()
+        .map_or(i32::MAX, |n| n.clamp(0, i32::MAX as i64) as i32);
+    let flags = args.get(2);
+    if flags.is_some_and(|f| f.has_flag("CLOSE_RANGE_UNSHARE")) {
+        ctx.unshare_fds(pid);
+    }
+    if !flags.is_some_and(|f| f.has_flag("CLOSE_RANGE_CLOEXEC")) {
+        ctx.close_fds(pid, first, last);
+    }
+    Ok(())
+}
+
+/// `pipe([r, w])`, `pipe2([r, w], flags)`.
+fn parse_pipe(pid: i32, args: &[Arg], ret: &str, ctx: &mut Context) -> Result<()> {
+    if is_ret_err(ret) {
+        return Ok(())
+    }
+    if let Arg::Array(fds) = nth_arg(args, 0)? {
+        for fd in fds {
+            if let Some(n) = fd_num(fd) {
+                ctx.set_fd(pid, n, fd.fd_path().unwrap_or("pipe:"));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_socket(pid: i32, ret: &str, ctx: &mut Context) {
+    let ret = parse_arg(ret);
+    if let Some(fd) = fd_num(&ret) {
+        ctx.set_fd(pid, fd, ret.fd_path().unwrap_or("socket:"));
+    }
+}
+
+/// Bytes a data call moved; `None` if it failed.
+fn transferred(ret: &str) -> Option<u64> {
+    ret.split_whitespace().next()?.parse().ok()
+}
+
+/// The file behind `fd`, or `None` for pipes, sockets and fds the trace
+/// never showed being opened.
+fn data_fd_path(pid: i32, fd: &Arg, ctx: &Context) -> Option<String> {
+    fd_annotation(pid, fd, ctx).ok().filter(|p| is_absolute_path(p))
+}
+
+/// A call that moves bytes from the fd at `input` and/or to the fd at
+/// `output`, e.g. `read(fd, ...)`, `sendfile(out, in, ...)`.
+fn parse_data(pid: i32, args: &[Arg], ret: &str, ctx: &Context, input: Option<usize>, output: Option<usize>) -> Result<Vec<RwFile>> {
+    let Some(n) = transferred(ret) else {
+        return Ok(vec![])
+    };
+    let mut files = Vec::new();
+    if let Some(i) = input
+        && let Some(path) = data_fd_path(pid, nth_arg(args, i)?, ctx)
+    {
+        files.push(RwFile::Read(RFile::new(&path).with_bytes(n)));
+    }
+    if let Some(o) = output
+        && let Some(path) = data_fd_path(pid, nth_arg(args, o)?, ctx)
+    {
+        files.push(RwFile::Write(WFile::new(&path).with_bytes(n)));
+    }
+    Ok(files)
+}
+
+/// `mmap(addr, length, prot, flags, fd, offset)`. A shared writable mapping
+/// writes the file; any other mapping of a file reads it. Anonymous and
+/// `PROT_NONE` mappings touch no file.
+fn parse_mmap(pid: i32, args: &[Arg], ret: &str, ctx: &Context) -> Result<Vec<RwFile>> {
+    if is_ret_err(ret) {
+        return Ok(vec![])
+    }
+    let prot = ProtFlags::from_arg(nth_arg(args, 2)?).unwrap_or_default();
+    let flags = MapFlags::from_arg(nth_arg(args, 3)?).unwrap_or_default();
+    if flags.contains(MapFlags::MAP_ANONYMOUS) || prot == ProtFlags::PROT_NONE {
+        return Ok(vec![])
+    }
+    let Some(path) = data_fd_path(pid, nth_arg(args, 4)?, ctx) else {
+        return Ok(vec![])
+    };
+    if flags.is_shared() && prot.contains(ProtFlags::PROT_WRITE) {
+        Ok(vec![RwFile::Write(WFile::new(&path))])
+    } else {
+        Ok(vec![RwFile::Read(RFile::new(&path))])
+    }
+}
+
+/// [`R_FD_SET`]/[`W_FD_SET`] calls. Fds that are not files, or that the
+/// trace never showed being opened (inherited stdio), are skipped.
+fn parse_fd(pid: i32, args: &[Arg], ret: &str, ctx: &Context, write: bool) -> Result<Vec<RwFile>> {
+    let Some(path) = data_fd_path(pid, nth_arg(args, 0)?, ctx) else {
+        return Ok(vec![])
+    };
+    if write && !is_ret_err(ret) {
+        Ok(vec![RwFile::Write(WFile::new(&path))])
+    } else {
+        Ok(vec![RwFile::Read(RFile::new(&path))])
+    }
+}
+
+/// `mount(source, target, fstype, flags, data)`: the target changes; a
+/// source that is a path (device, bind mount) is read.
+fn parse_mount(pid: i32, args: &[Arg], ret: &str, ctx: &mut Context) -> Result<Vec<RwFile>> {
+    let target = path_str(&get_path_at(pid, ArgPos::Single(1), args, ctx)?[0]);
+    let mut files = Vec::new();
+    if let Some(source) = nth_arg(args, 0)?.as_str()
+        && is_absolute_path(source)
+    {
+        files.push(RwFile::Read(RFile::new(source)));
+    }
+    if is_ret_err(ret) {
+        files.push(RwFile::Read(RFile::new(&target)));
+    } else {
+        files.push(RwFile::Write(WFile::new(&target)));
+    }
+    Ok(files)
+}
+
+/// `fsconfig(fd, cmd, key, value, aux)`. `FSCONFIG_SET_PATH` names a file
+/// relative to the dirfd in `aux` (with `FSCONFIG_SET_PATH_EMPTY`, an empty
+/// value is `aux` itself); a `source` set as a string is read like `mount`'s.
+fn parse_fsconfig(pid: i32, args: &[Arg], _ret: &str, ctx: &mut Context) -> Result<Vec<RwFile>> {
+    let cmd = nth_arg(args, 1)?;
+    let value = nth_arg(args, 3)?;
+    let path = if cmd.has_flag("FSCONFIG_SET_PATH") || cmd.has_flag("FSCONFIG_SET_PATH_EMPTY") {
+        let value = parse_string(value)?;
+        if value.is_empty() && !cmd.has_flag("FSCONFIG_SET_PATH_EMPTY") {
+            return Ok(vec![])
+        }
+        resolve_at(pid, nth_arg(args, 4)?, value, ctx)?
+    } else if cmd.has_flag("FSCONFIG_SET_STRING") && nth_arg(args, 2)?.as_str() == Some("source") {
+        value.as_str().filter(|s| is_absolute_path(s)).map(PathBuf::from)
+    } else {
+        None
+    };
+    Ok(path.iter().map(|p| RwFile::Read(RFile::new(&path_str(p)))).collect())
+}
+
+/// `pivot_root(new_root, put_old)` moves mounts at both paths.
+fn parse_pivot_root(pid: i32, args: &[Arg], ret: &str, ctx: &mut Context) -> Result<Vec<RwFile>> {
+    let paths = get_path_at(pid, ArgPos::Multiple(vec![0, 1]), args, ctx)?;
+    Ok(paths
+        .iter()
+        .map(|p| {
+            let p = path_str(p);
+            if is_ret_err(ret) {
+                RwFile::Read(RFile::new(&p))
+            } else {
+                RwFile::Write(WFile::new(&p))
+            }
+        })
+        .collect())
+}
+
+/// `move_mount(from_dirfd, from_path, to_dirfd, to_path, flags)`.
+fn parse_move_mount(pid: i32, args: &[Arg], ret: &str, ctx: &mut Context) -> Result<Vec<RwFile>> {
+    let paths = fd_path_pair(pid, args, ctx)?;
+    if is_ret_err(ret) {
+        return Ok(paths.iter().map(|p| RwFile::Read(RFile::new(p))).collect())
+    }
+    Ok(paths.iter().map(|p| RwFile::Write(WFile::new(p))).collect())
+}
+
+/// `open_tree(dirfd, path, flags)` returns an fd for the mount at `path`.
+fn parse_open_tree(pid: i32, args: &[Arg], ret: &str, ctx: &mut Context) -> Result<Vec<RwFile>> {
+    let Some(path) = get_path_from_fd_path(pid, args, ctx)?.map(|p| path_str(&p)) else {
+        return Ok(vec![])
+    };
+    if let Some(fd) = fd_num(&parse_arg(ret)) {
+        ctx.set_fd(pid, fd, &path);
+    }
+    Ok(vec![RwFile::Read(RFile::new(&path))])
+}
+
+/// `open_by_handle_at(mount_fd, handle, flags)`: the path is only known from
+/// the returned fd's `-y` annotation.
+fn parse_open_by_handle_at(pid: i32, args: &[Arg], ret: &str, ctx: &mut Context) -> Result<Vec<RwFile>> {
+    let flags = nth_arg(args, 2)?;
+    match parse_arg(ret).fd_path() {
+        Some(path) => handle_open_common(pid, PathBuf::from(path), flags, ret, ctx),
+        None => Ok(vec![]),
+    }
+}
+
+/// `fanotify_mark(fd, flags, mask, dirfd, path)`; a `NULL` path marks `dirfd`.
+fn parse_fanotify_mark(pid: i32, args: &[Arg], _ret: &str, ctx: &mut Context) -> Result<Vec<RwFile>> {
+    let dirfd = nth_arg(args, 3)?;
+    let path = parse_string(nth_arg(args, 4)?)?;
+    let path = resolve_at(pid, dirfd, path, ctx)?;
+    Ok(path.iter().map(|p| RwFile::Read(RFile::new(&path_str(p)))).collect())
+}
+
+/// `quotactl(cmd, special, id, addr)`: `special` is the block device.
+fn parse_quotactl(pid: i32, args: &[Arg], _ret: &str, ctx: &mut Context) -> Result<Vec<RwFile>> {
+    match nth_arg(args, 1)?.as_str() {
+        Some("") | None => Ok(vec![]),
+        Some(_) => Ok(vec![RwFile::Read(RFile::new(&path_str(&get_path_at(pid, ArgPos::Single(1), args, ctx)?[0])))]),
+    }
+}
+
+/// `bind`/`connect` on a Unix socket with a filesystem path: `bind` creates
+/// the socket file, `connect` looks it up. Abstract (`@...`) and network
+/// addresses touch no file.
+fn parse_sockaddr(pid: i32, args: &[Arg], ret: &str, ctx: &mut Context, bind: bool) -> Result<Vec<RwFile>> {
+    let Some(path) = nth_arg(args, 1)?.field("sun_path").and_then(Arg::as_str) else {
+        return Ok(vec![])
+    };
+    if path.is_empty() || path.starts_with('@') {
+        return Ok(vec![])
+    }
+    let path = path_str(&convert_absolute(Path::new(&ctx.get_dir(pid)), path));
+    if bind && !is_ret_err(ret) {
+        Ok(vec![RwFile::Write(WFile::new(&path))])
+    } else {
+        Ok(vec![RwFile::Read(RFile::new(&path))])
+    }
+}
+
+/// `fchdir(fd)` moves to wherever `fd` points.
+fn parse_fchdir(pid: i32, args: &[Arg], ret: &str, ctx: &mut Context) -> Result<RwFile> {
+    let path = fd_annotation(pid, nth_arg(args, 0)?, ctx)?;
+    if !is_ret_err(ret) {
+        ctx.set_dir(&path, Some(pid));
+    }
+    Ok(RwFile::Read(RFile::new(&path)))
+}
+
+
+fn parse_symlinkat(pid: i32, args: &[Arg], ret :&str, ctx: &mut Context) -> Result<Vec<RwFile>> {
+    parse_w_fd_path(pid, skip_first(args)?, ret, None, ctx)
+}
+
+fn parse_symlink(pid:i32, args: &[Arg], ret :&str, ctx: &mut Context) -> Result<RwFile> {
+    parse_w_first_path(pid, skip_first(args)?, ret, ctx)
+}
+
+fn parse_inotify_add_watch(pid:i32, args: &[Arg], ret :&str, ctx: &mut Context) -> Result<RwFile> {
+    parse_r_first_path(pid, skip_first(args)?, ret, ctx)
+}
+
+/// A two-path call classified by a [`Rule`]: both paths get its access.
+fn parse_two_paths(pid: i32, args: &[Arg], ret: &str, ctx: &mut Context, write: bool) -> Result<Vec<RwFile>> {
+    let paths = get_path_at(pid, ArgPos::Multiple(vec![0, 1]), args, ctx)?;
+    Ok(paths
+        .iter()
+        .map(|p| {
+            let p = path_str(p);
+            if write && !is_ret_err(ret) {
+                RwFile::Write(WFile::new(&p))
+            } else {
+                RwFile::Read(RFile::new(&p))
+            }
+        })
+        .collect())
+}
+
+/// Classify a call by a rule from the context's [`crate::table::SyscallTable`].
+fn parse_by_rule(pid: i32, rule: Rule, args: &[Arg], ret: &str, ctx: &mut Context) -> Result<Vec<RwFile>> {
+    let access = rule.access.unwrap_or(Semantics::None);
+    match (rule.layout, access) {
+        (Layout::Ignore, _) => Ok(vec![]),
+        (Layout::Open, _) => parse_open(pid, args, ret, ctx),
+        (Layout::Openat, _) => parse_openat(pid, args, ret, ctx),
+        (Layout::FirstPath, Semantics::Read) => Ok(vec![parse_r_first_path(pid, args, ret, ctx)?]),
+        (Layout::FirstPath, Semantics::Write) => Ok(vec![parse_w_first_path(pid, args, ret, ctx)?]),
+        (Layout::FirstPath, Semantics::None) => get_path_first_path(pid, args, ctx).map(|_| vec![]),
+        (Layout::FdPath, Semantics::Read) => parse_r_fd_path(pid, args, ret, None, ctx),
+        (Layout::FdPath, Semantics::Write) => parse_w_fd_path(pid, args, ret, None, ctx),
+        (Layout::FdPath, Semantics::None) => get_path_from_fd_path(pid, args, ctx).map(|_| vec![]),
+        (Layout::TwoPaths, Semantics::None) => get_path_at(pid, ArgPos::Multiple(vec![0, 1]), args, ctx).map(|_| vec![]),
+        (Layout::TwoPaths, access) => parse_two_paths(pid, args, ret, ctx, access == Semantics::Write),
+    }
+}
+
+/// Classify one completed syscall. Calls that touch no files (`clone`,
+/// `dup`, `close`, [`IGNORE_SET`]) yield an empty list; unknown ones are
+/// [`ParseErrorKind::UnclassifiedSyscall`]. Rules in the context's
+/// [`crate::table::SyscallTable`] come first.
+pub fn parse_syscall(pid: i32, syscall: &str, args:  &str, ret :&str, ctx: &mut Context)-> Result<Vec<RwFile>>{
+    parse_syscall_args(pid, syscall, &parse_args(args), ret, ctx)
+}
+
+/// [`parse_syscall`] on arguments that are already parsed.
+fn parse_syscall_args(pid: i32, syscall: &str, args: &[Arg], ret: &str, ctx: &mut Context) -> Result<Vec<RwFile>> {
+    if let Some(&rule) = ctx.syscall_table().get(syscall) {
+        return parse_by_rule(pid, rule, args, ret, ctx);
+    }
+    match syscall {
+        "creat" => parse_creat(pid, args, ret, ctx),
+        "execve" => parse_execve(pid, args, ret, ctx),
+        "execveat" => parse_execveat(pid, args, ret, ctx),
+        s if R_FIRST_PATH_SET.contains(s) => Ok(vec![parse_r_first_path(pid, args, ret, ctx)?]),
+        s if W_FIRST_PATH_SET.contains(s) => Ok(vec![parse_w_first_path(pid, args, ret, ctx)?]),
+        "openat" => parse_openat(pid, args, ret, ctx),
+        "openat2" => parse_openat2(pid, args, ret, ctx),
+        "chdir" => Ok(vec![parse_chdir(pid, args, ret, ctx)?]),
+        "open" => parse_open(pid, args, ret, ctx),
+        s if R_FD_PATH_SET.contains(s) => parse_r_fd_path(pid, args, ret, at_flags(s, args), ctx),
+        s if W_FD_PATH_SET.contains(s) => parse_w_fd_path(pid, args, ret, at_flags(s, args), ctx),
+        "rename" => parse_rename(pid, args, ret, ctx),
+        "renameat" | "renameat2" => parse_renameat(pid, args, ret, ctx),
+        "symlinkat" => parse_symlinkat(pid, args, ret, ctx),
+        "symlink" => Ok(vec![parse_symlink(pid, args, ret, ctx)?]),
+        "link" => parse_link(pid, args, ret, ctx),
+        "linkat" => parse_linkat(pid, args, ret, ctx),
+        "clone" | "clone3" => parse_clone(pid, args, ret, ctx).map(|_| vec![]),
+        "unshare" => parse_unshare(pid, args, ret, ctx).map(|_| vec![]),
+        "fork" | "vfork" => {
+            parse_fork(pid, ret, ctx);
+            Ok(vec![])
+        }
+        "dup" | "dup2" | "dup3" => parse_dup(pid, args, ret, ctx).map(|_| vec![]),
+        "fcntl" | "fcntl64" => parse_fcntl(pid, args, ret, ctx).map(|_| vec![]),
+        "close" => parse_close(pid, args, ctx).map(|_| vec![]),
+        "close_range" => parse_close_range(pid, args, ret, ctx).map(|_| vec![]),
+        "pipe" | "pipe2" => parse_pipe(pid, args, ret, ctx).map(|_| vec![]),
+        "socket" => {
+            parse_socket(pid, ret, ctx);
+            Ok(vec![])
+        }
+        "fchdir" => Ok(vec![parse_fchdir(pid, args, ret, ctx)?]),
+        "read" | "readv" | "pread64" | "preadv" | "preadv2" => parse_data(pid, args, ret, ctx, Some(0), None),
+        "write" | "writev" | "pwrite64" | "pwritev" | "pwritev2" => parse_data(pid, args, ret, ctx, None, Some(0)),
+        "sendfile" | "sendfile64" => parse_data(pid, args, ret, ctx, Some(1), Some(0)),
+        "splice" | "copy_file_range" => parse_data(pid, args, ret, ctx, Some(0), Some(2)),
+        "tee" => parse_data(pid, args, ret, ctx, Some(0), Some(1)),
+        s if R_FD_SET.contains(s) => parse_fd(pid, args, ret, ctx, false),
+        s if W_FD_SET.contains(s) => parse_fd(pid, args, ret, ctx, true),
+        "mount" => parse_mount(pid, args, ret, ctx),
+        "pivot_root" => parse_pivot_root(pid, args, ret, ctx),
+        "move_mount" => parse_move_mount(pid, args, ret, ctx),
+        "open_tree" => parse_open_tree(pid, args, ret, ctx),
+        "fsconfig" => parse_fsconfig(pid, args, ret, ctx),
+        "open_by_handle_at" => parse_open_by_handle_at(pid, args, ret, ctx),
+        "fanotify_mark" => parse_fanotify_mark(pid, args, ret, ctx),
+        "quotactl" => parse_quotactl(pid, args, ret, ctx),
+        "bind" => parse_sockaddr(pid, args, ret, ctx, true),
+        "connect" => parse_sockaddr(pid, args, ret, ctx, false),
+        "mmap" | "mmap2" => parse_mmap(pid, args, ret, ctx),
+        "inotify_add_watch" => Ok(vec![parse_inotify_add_watch(pid, args, ret, ctx)?]),
+        s if IGNORE_SET.contains(s) => Ok(vec![]),
+        _ => Err(ParseErrorKind::UnclassifiedSyscall),
+    }
+}
+
+pub fn strip_pid(l: &str) -> Result<(i32, &str)>{
+    let (pid, rest) = split_pid(l).ok_or(ParseErrorKind::MissingPid)?;
+    let pid = pid.parse().map_err(|_| ParseErrorKind::BadPid(pid.to_string()))?;
+    Ok((pid, rest))
+}
+
+pub fn handle_info(l: &str) -> Result<(bool, Option<ExitStatus>)> {
+    if l.ends_with("+++") && parse_superseded(l).is_some() {
+        Ok((true, None))
+    }
+    else if l.ends_with("+++") {
+        Ok((true, Some(parse_info(l)?)))
+    }
+    else if l.ends_with("---") {
+        Ok((true, None))
+    }
+    else {
+        Ok((false, None))
+    }
+
+}
+
+/// What [`parse_line`] found on a line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LineResult {
+    None,
+    Info(Option<ExitStatus>),
+    Files(Vec<RwFile>),
+}
+
+/// A completed syscall from one trace line (or a joined
+/// `<unfinished ...>`/`<... resumed>` pair), not yet classified.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyscallLine {
+    /// Line number the call completed on.
+    pub line: usize,
+    pub pid: i32,
+    pub syscall: String,
+    pub args: String,
+    pub ret: String,
+}
+
+/// Renders as the equivalent strace line, `pid name(args) = ret`.
+impl fmt::Display for SyscallLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}({}) = {}", self.pid, self.syscall, self.args, self.ret.trim_start())
+    }
+}
+
+pub(crate) enum Parsed {
+    Skip,
+    Info(i32, Option<ExitStatus>),
+    Signal(i32, Signal),
+    Call(SyscallLine),
+}
+
+/// Split a trace line into pid, syscall, arguments and return value,
+/// stitching together unfinished calls. Every call advances
+/// [`Context::line_no`], which is what errors are reported against.
+pub(crate) fn split_line(l: &str, ctx: &mut Context) -> std::result::Result<Parsed, ParseError> {
+    ctx.line_no += 1;
+    let mut err = ParseError {
+        kind: ParseErrorKind::MissingPid,
+        line: ctx.line_no,
+        pid: None,
+        syscall: None,
+        text: l.to_string(),
+    };
+
+    if l.is_empty() {
+        return Ok(Parsed::Skip)
+    };
+    let (pid, l) = strip_pid(l).map_err(|kind| ParseError { kind, ..err.clone() })?;
+    err.pid = Some(pid);
+    if l.is_empty() {
+        return Ok(Parsed::Skip)
+    }
+    let (is_info, info) = handle_info(l).map_err(|kind| ParseError { kind, ..err.clone() })?;
+    if is_info {
+        if let Some(status) = &info {
+            ctx.process_exited(pid, status.clone());
+        } else if let Some(tid) = parse_superseded(l) {
+            ctx.thread_exec(pid, tid);
+        } else if let Some(signal) = parse_signal(l) {
+            ctx.signal_received(pid, signal.clone());
+            return Ok(Parsed::Signal(pid, signal))
+        }
+        return Ok(Parsed::Info(pid, info))
+    };
+
+    if l.contains("<unfinished") {
+        ctx.push_half_line(pid, l);
+        return Ok(Parsed::Skip)
+    }
+    let joined;
+    let l =  if l.contains("resumed>") {
+        joined = ctx.pop_complete_line(pid, l).map_err(|kind| ParseError { kind, ..err })?;
+        joined.as_str()
+    } else {
+        l
+    };
+
+    let Some((syscall, args, ret)) = split_call(l) else {
+        return Ok(Parsed::Skip);
+    };
+
+    Ok(Parsed::Call(SyscallLine {
+        line: ctx.line_no,
+        pid,
+        syscall: syscall.to_string(),
+        args: args.to_string(),
+        ret: ret.to_string(),
+    }))
+}
+
+/// Classify a split line with arguments `args`, turning handler failures
+/// into a located [`ParseError`]. `None` for unclassified syscalls.
+pub(crate) fn classify(call: &SyscallLine, args: &[Arg], text: &str, ctx: &mut Context) -> std::result::Result<Option<Vec<RwFile>>, ParseError> {
+    ctx.saw_pid(call.pid);
+    match parse_syscall_args(call.pid, &call.syscall, args, &call.ret, ctx) {
+        Ok(files) => Ok(Some(files)),
+        Err(ParseErrorKind::UnclassifiedSyscall) => Ok(None),
+        Err(kind) => Err(ParseError {
+            kind,
+            line: call.line,
+            pid: Some(call.pid),
+            syscall: Some(call.syscall.clone()),
+            text: text.to_string(),
+        }),
+    }
+}
+
+/// Parse the next line of the trace. Every call advances
+/// [`Context::line_no`], which is what errors are reported against.
+pub fn parse_line(l: &str, ctx: &mut Context) -> std::result::Result<LineResult, ParseError> {
+    match split_line(l, ctx)? {
+        Parsed::Skip => Ok(LineResult::None),
+        Parsed::Info(_, info) => Ok(LineResult::Info(info)),
+        Parsed::Signal(..) => Ok(LineResult::Info(None)),
+        Parsed::Call(call) => match classify(&call, &parse_args(&call.args), l, ctx)? {
+            Some(files) => Ok(LineResult::Files(files)),
+            None => Ok(LineResult::None),
+        },
+    }
+}
+
+
+/// Everything [`parse_line`] learns from one line, for callers that need
+/// more than the file list.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Record {
+    /// Blank line, `<unfinished ...>` half, or nothing to report.
+    Skip,
+    /// A `+++` line for `pid`, or a `---` line that is not a signal
+    /// delivery; `status` is set for `+++` lines that end a process.
+    Info { pid: i32, status: Option<ExitStatus> },
+    /// A signal delivered to `pid`.
+    Signal { pid: i32, signal: Signal },
+    /// A completed call; `files` is `None` for unclassified syscalls.
+    Call { call: SyscallLine, files: Option<Vec<RwFile>> },
+}
+
+pub fn parse_record(l: &str, ctx: &mut Context) -> std::result::Result<Record, ParseError> {
+    match split_line(l, ctx)? {
+        Parsed::Skip => Ok(Record::Skip),
+        Parsed::Info(pid, status) => Ok(Record::Info { pid, status }),
+        Parsed::Signal(pid, signal) => Ok(Record::Signal { pid, signal }),
+        Parsed::Call(call) => {
+            let files = classify(&call, &parse_args(&call.args), l, ctx)?;
+            Ok(Record::Call { call, files })
+        }
+    }
+}
+
+/// Classify a call that did not come from a text line, such as one reported
+/// by [`crate::tracer::Tracer`], whose arguments are already decoded into
+/// `args`; `call.args` is only used to report errors.
+pub fn parse_call(call: SyscallLine, args: &[Arg], ctx: &mut Context) -> std::result::Result<Record, ParseError> {
+    let text = call.to_string();
+    let files = classify(&call, args, &text, ctx)?;
+    Ok(Record::Call { call, files })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// `r path` or `w path` for each file `trace` touches, from `/w`.
+    pub(crate) fn accesses(trace: &str) -> Vec<String> {
+        let mut ctx = Context::new();
+        ctx.set_dir("/w", None);
+        let mut out = Vec::new();
+        for l in trace.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if let LineResult::Files(files) = parse_line(l, &mut ctx).unwrap() {
+                out.extend(files.iter().map(|f| match f {
+                    RwFile::Read(r) => format!("r {}", r.fname),
+                    RwFile::Write(w) => format!("w {}", w.fname),
+                }));
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn at_paths_resolve_against_cwd_and_dirfd() {
+        let trace = r#"
+            1 chdir("/w/sub") = 0
+            1 newfstatat(AT_FDCWD, "f", {st_mode=S_IFREG|0644, ...}, 0) = 0
+            1 openat(AT_FDCWD, "/d", O_RDONLY|O_DIRECTORY) = 3
+            1 newfstatat(3, "g", {st_mode=S_IFREG|0644, ...}, 0) = 0
+            1 unlinkat(3, "h", 0) = 0
+            1 renameat(3, "a", AT_FDCWD, "b") = 0
+        "#;
+        let expected = ["r /w/sub", "r /w/sub/f", "r /d", "r /d/g", "w /d/h", "w /d/a", "w /w/sub/b"];
+        assert_eq!(accesses(trace), expected);
+    }
+
+    #[test]
+    fn empty_path_is_the_fd_only_with_at_empty_path() {
+        let trace = r#"
+            1 newfstatat(3</d>, "", {st_mode=S_IFDIR|0755, ...}, AT_EMPTY_PATH) = 0
+            1 newfstatat(3</d>, "", 0x7ff, 0) = -1 ENOENT (No such file or directory)
+            1 utimensat(4</u>, NULL, NULL, 0) = 0
+        "#;
+        assert_eq!(accesses(trace), ["r /d", "w /u"]);
+    }
+
+    #[test]
+    fn pipe_and_socket_fds_name_no_file() {
+        let trace = r#"
+            1 pipe2([3, 4], O_CLOEXEC) = 0
+            1 newfstatat(3, "", {st_mode=S_IFIFO|0600, ...}, AT_EMPTY_PATH) = 0
+            1 newfstatat(4<pipe:[10]>, "", {st_mode=S_IFIFO|0600, ...}, AT_EMPTY_PATH) = 0
+            1 socket(AF_UNIX, SOCK_STREAM, 0) = 5
+            1 newfstatat(5, "", {st_mode=S_IFSOCK|0777, ...}, AT_EMPTY_PATH) = 0
+            1 openat(6<socket:[11]>, "x", O_RDONLY) = -1 ENOTDIR (Not a directory)
+        "#;
+        assert_eq!(accesses(trace), Vec::<String>::new());
+    }
+
+    #[test]
+    fn openat2_is_classified_like_openat() {
+        let trace = r#"
+            1 openat2(AT_FDCWD, "a", {flags=O_RDONLY|O_CLOEXEC, resolve=0}, 24) = 3
+            1 openat2(AT_FDCWD, "b", {flags=O_RDWR|O_CREAT, mode=0644, resolve=0}, 24) = 4
+            1 read(3, "x", 1) = 1
+        "#;
+        assert_eq!(accesses(trace), ["r /w/a", "r /w/b", "w /w/b", "r /w/a"]);
+    }
+
+    #[test]
+    fn openat2_resolve_flags_keep_paths_under_the_dirfd() {
+        let trace = r#"
+            1 openat2(4</root>, "/etc/passwd", {flags=O_RDONLY, resolve=RESOLVE_IN_ROOT}, 24) = 5
+            1 openat2(4</root>, "../../x", {flags=O_WRONLY|O_CREAT, mode=0644, resolve=RESOLVE_IN_ROOT|RESOLVE_NO_SYMLINKS}, 24) = 6
+            1 openat2(4</root>, "sub/../f", {flags=O_RDONLY, resolve=RESOLVE_BENEATH}, 24) = 7
+        "#;
+        assert_eq!(accesses(trace), ["r /root/etc/passwd", "w /root/x", "r /root/f"]);
+    }
+
+    #[test]
+    fn data_calls_count_bytes_per_fd() {
+        let trace = r#"
+            1 openat(AT_FDCWD, "in", O_RDWR) = 3
+            1 openat(AT_FDCWD, "out", O_WRONLY|O_CREAT, 0644) = 4
+            1 read(3, "hello", 5) = 5
+            1 read(3, 0x7ff, 5) = -1 EAGAIN (Resource temporarily unavailable)
+            1 write(4, "hi", 2) = 2
+            1 sendfile(4, 3, NULL, 100) = 100
+            1 splice(3, NULL, 4, NULL, 10, 0) = 10
+            1 ftruncate(4, 0) = 0
+        "#;
+        let mut ctx = Context::new();
+        ctx.set_dir("/w", None);
+        let mut transfers = Vec::new();
+        for l in trace.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if let LineResult::Files(files) = parse_line(l, &mut ctx).unwrap()
+                && !l.contains("openat")
+            {
+                transfers.extend(files.iter().map(|f| (matches!(f, RwFile::Write(_)), f.fname().to_string(), f.bytes())));
+            }
+        }
+        let expected = [
+            (false, "/w/in", Some(5)),
+            (true, "/w/out", Some(2)),
+            (false, "/w/in", Some(100)),
+            (true, "/w/out", Some(100)),
+            (false, "/w/in", Some(10)),
+            (true, "/w/out", Some(10)),
+            (true, "/w/out", None),
+        ];
+        assert_eq!(transfers, expected.map(|(w, p, n)| (w, p.to_string(), n)));
+    }
+
+    #[test]
+    fn shared_writable_mappings_are_writes() {
+        let trace = r#"
+            1 openat(AT_FDCWD, "db", O_RDWR) = 3
+            1 mmap(NULL, 4096, PROT_READ|PROT_WRITE, MAP_SHARED, 3, 0) = 0x7f00
+            1 mmap(NULL, 4096, PROT_READ|PROT_WRITE, MAP_PRIVATE, 3, 0) = 0x7f10
+            1 mmap(NULL, 4096, PROT_READ, MAP_SHARED, 4</lib.so>, 0) = 0x7f20
+            1 mmap(NULL, 4096, PROT_READ|PROT_WRITE, MAP_PRIVATE|MAP_ANONYMOUS, -1, 0) = 0x7f30
+            1 mmap(NULL, 4096, PROT_READ|PROT_WRITE, MAP_SHARED, 3, 0) = -1 ENOMEM (Cannot allocate memory)
+        "#;
+        assert_eq!(accesses(trace), ["r /w/db", "w /w/db", "w /w/db", "r /w/db", "r /lib.so"]);
+    }
+
+    #[test]
+    fn dot_components_are_dropped() {
+        let trace = r#"
+            1 newfstatat(AT_FDCWD</tmp>, ".", {st_mode=S_IFDIR|0777, ...}, 0) = 0
+            1 stat("./a/./b", {st_mode=S_IFREG|0644, ...}) = 0
+            1 openat(3</d>, "./x/.", O_RDONLY|O_DIRECTORY) = 4</d/x>
+            1 mkdirat(AT_FDCWD, "c/./", 0755) = 0
+        "#;
+        assert_eq!(accesses(trace), ["r /tmp", "r /w/a/b", "r /d/x", "w /w/c"]);
+    }
+}
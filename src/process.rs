@@ -0,0 +1,458 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::flags::CloneFlags;
+use crate::parse::{ExitStatus, Signal};
+
+/// A program a process started running with a successful `execve` or
+/// `execveat`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct Exec {
+    pub exe: String,
+    pub argv: Vec<String>,
+}
+
+impl Exec {
+    /// `argv` joined by spaces, or the executable if `argv` is empty.
+    pub fn command_line(&self) -> String {
+        if self.argv.is_empty() {
+            self.exe.clone()
+        } else {
+            self.argv.join(" ")
+        }
+    }
+}
+
+/// One traced task, process or thread.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Process {
+    pub pid: i32,
+    /// The process that created it. A thread hangs under its thread-group
+    /// leader, and a `CLONE_PARENT` child under its creator's parent. `None`
+    /// for pids whose creation the trace did not show.
+    pub parent: Option<i32>,
+    /// Thread-group id: `pid` itself unless cloned with `CLONE_THREAD`.
+    pub tgid: i32,
+    pub children: Vec<i32>,
+    /// The program it is running: its own last exec, or the one it
+    /// inherited from its creator. `None` if the trace never showed one.
+    pub exec: Option<Exec>,
+    /// Programs this pid itself exec'd, in order.
+    pub execs: Vec<Exec>,
+    /// How it ended; `None` if it was still running when the trace did.
+    pub exit: Option<ExitStatus>,
+    /// Signals delivered to it, in order.
+    pub signals: Vec<Signal>,
+    /// Index into [`ProcessTree`]'s working directories; tasks cloned with
+    /// `CLONE_FS` share one. `None` once it has exited.
+    #[serde(skip)]
+    fs: Option<usize>,
+    /// Set once it changes directory itself, so a clone that resolves
+    /// later does not undo that.
+    #[serde(skip)]
+    own_cwd: bool,
+}
+
+impl Process {
+    pub fn is_thread(&self) -> bool {
+        self.tgid != self.pid
+    }
+
+    /// Whether the trace ended before this pid did.
+    pub fn is_running(&self) -> bool {
+        self.exit.is_none()
+    }
+}
+
+/// A working directory and how many live tasks share it.
+#[derive(Debug, Clone, Default)]
+struct Fs {
+    cwd: String,
+    users: usize,
+}
+
+/// Every pid seen in a trace, linked by who created whom, with the working
+/// directory of each group of tasks that share fs state.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessTree {
+    procs: HashMap<i32, Process>,
+    cwds: Vec<Fs>,
+    /// Slots of `cwds` no task uses any more, for [`Self::new_fs`] to reuse.
+    free: Vec<usize>,
+    /// Working directory of pids the trace never showed being created.
+    initial_cwd: String,
+}
+
+impl ProcessTree {
+    pub fn new() -> ProcessTree {
+        ProcessTree::default()
+    }
+
+    pub fn set_initial_cwd(&mut self, path: &str) {
+        self.initial_cwd = path.to_string();
+    }
+
+    /// A slot for `cwd` with one user.
+    fn new_fs(&mut self, cwd: String) -> usize {
+        let fs = Fs { cwd, users: 1 };
+        match self.free.pop() {
+            Some(i) => {
+                self.cwds[i] = fs;
+                i
+            }
+            None => {
+                self.cwds.push(fs);
+                self.cwds.len() - 1
+            }
+        }
+    }
+
+    /// Drop one user of slot `fs`, freeing it once nobody is left.
+    fn release(&mut self, fs: Option<usize>) {
+        let Some(fs) = fs else {
+            return;
+        };
+        let slot = &mut self.cwds[fs];
+        slot.users -= 1;
+        if slot.users == 0 {
+            slot.cwd = String::new();
+            self.free.push(fs);
+        }
+    }
+
+    /// Move `pid` onto slot `fs`, which already counts it as a user.
+    fn set_fs(&mut self, pid: i32, fs: usize) {
+        let old = self.ensure(pid).fs.replace(fs);
+        self.release(old);
+    }
+
+    /// The slot of `pid`, given a fresh one in the initial working
+    /// directory if it has none.
+    fn fs(&mut self, pid: i32) -> usize {
+        if let Some(fs) = self.ensure(pid).fs {
+            return fs;
+        }
+        let fs = self.new_fs(self.initial_cwd.clone());
+        self.ensure(pid).fs = Some(fs);
+        fs
+    }
+
+    /// `pid`, created as a root with the initial working directory if the
+    /// trace has not mentioned it before.
+    fn ensure(&mut self, pid: i32) -> &mut Process {
+        if !self.procs.contains_key(&pid) {
+            let fs = self.new_fs(self.initial_cwd.clone());
+            self.procs.insert(
+                pid,
+                Process {
+                    pid,
+                    parent: None,
+                    tgid: pid,
+                    children: Vec::new(),
+                    exec: None,
+                    execs: Vec::new(),
+                    exit: None,
+                    signals: Vec::new(),
+                    fs: Some(fs),
+                    own_cwd: false,
+                },
+            );
+        }
+        self.procs.get_mut(&pid).expect("just inserted")
+    }
+
+    /// Add `pid` as a root if the trace has not mentioned it before.
+    pub fn add(&mut self, pid: i32) {
+        self.ensure(pid);
+    }
+
+    /// Add `child`, whose calls showed up before `parent`'s clone returned,
+    /// in a copy of `parent`'s working directory. [`Self::clone_process`]
+    /// links it up once the clone does return.
+    pub fn add_early_child(&mut self, parent: i32, child: i32) {
+        if self.procs.contains_key(&child) {
+            return;
+        }
+        let cwd = self.cwd(parent).to_string();
+        let fs = self.new_fs(cwd);
+        self.set_fs(child, fs);
+    }
+
+    pub fn get(&self, pid: i32) -> Option<&Process> {
+        self.procs.get(&pid)
+    }
+
+    /// All pids, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &Process> {
+        self.procs.values()
+    }
+
+    /// Pids with no known parent, in ascending order.
+    pub fn roots(&self) -> Vec<i32> {
+        let mut roots: Vec<i32> = self.procs.values().filter(|p| p.parent.is_none()).map(|p| p.pid).collect();
+        roots.sort_unstable();
+        roots
+    }
+
+    /// `pid` and everything it created, directly or not, parents first.
+    pub fn subtree(&self, pid: i32) -> Vec<i32> {
+        let mut out = Vec::new();
+        let mut seen = HashSet::new();
+        let mut stack = vec![pid];
+        while let Some(p) = stack.pop() {
+            if !seen.insert(p) {
+                continue;
+            }
+            out.push(p);
+            if let Some(proc) = self.procs.get(&p) {
+                stack.extend(proc.children.iter().rev());
+            }
+        }
+        out
+    }
+
+    /// Record that `parent` created `child` with `flags` (empty for `fork`
+    /// and `vfork`). A child already seen, because its first calls were
+    /// logged before the parent's returned, is moved into place and keeps
+    /// what it did meanwhile; it only takes the parent's working directory
+    /// if it has not changed its own.
+    pub fn clone_process(&mut self, parent: i32, child: i32, flags: CloneFlags) {
+        let caller_fs = self.fs(parent);
+        let (caller_parent, caller_tgid, caller_exec) = {
+            let p = self.ensure(parent);
+            (p.parent, p.tgid, p.exec.clone())
+        };
+        let thread = flags.contains(CloneFlags::CLONE_THREAD);
+        let (link, tgid) = if thread {
+            (Some(caller_tgid), caller_tgid)
+        } else if flags.contains(CloneFlags::CLONE_PARENT) {
+            (caller_parent, child)
+        } else {
+            (Some(parent), child)
+        };
+        let own_cwd = self.procs.get(&child).filter(|c| c.own_cwd).and_then(|c| c.fs).map(|fs| self.cwds[fs].cwd.clone());
+        match (flags.contains(CloneFlags::CLONE_FS), own_cwd) {
+            (true, own_cwd) => {
+                if let Some(cwd) = own_cwd {
+                    self.cwds[caller_fs].cwd = cwd;
+                }
+                self.cwds[caller_fs].users += 1;
+                self.set_fs(child, caller_fs);
+            }
+            (false, Some(_)) => {}
+            (false, None) => {
+                let fs = self.new_fs(self.cwds[caller_fs].cwd.clone());
+                self.set_fs(child, fs);
+            }
+        }
+
+        let old_parent = self.ensure(child).parent;
+        if let Some(old) = old_parent
+            && let Some(p) = self.procs.get_mut(&old)
+        {
+            p.children.retain(|&c| c != child);
+        }
+        let proc = self.ensure(child);
+        proc.parent = link;
+        proc.tgid = tgid;
+        if proc.execs.is_empty() {
+            proc.exec = caller_exec;
+        }
+        if let Some(link) = link {
+            self.ensure(link).children.push(child);
+        }
+    }
+
+    /// `pid` replaced its program with `exec`.
+    pub fn exec(&mut self, pid: i32, exec: Exec) {
+        let proc = self.ensure(pid);
+        proc.exec = Some(exec.clone());
+        proc.execs.push(exec);
+    }
+
+    /// Thread `tid` exec'd and took over `leader`'s pid: its history moves
+    /// onto `leader`, and `tid` is no longer a pid of its own.
+    pub fn thread_exec(&mut self, leader: i32, tid: i32) {
+        if tid == leader {
+            return;
+        }
+        let Some(thread) = self.procs.remove(&tid) else {
+            return;
+        };
+        for pid in thread.parent.iter().chain(&thread.children) {
+            if let Some(p) = self.procs.get_mut(pid) {
+                p.children.retain(|&c| c != tid);
+                if p.parent == Some(tid) {
+                    p.parent = Some(leader);
+                }
+            }
+        }
+        if let Some(fs) = thread.fs {
+            self.set_fs(leader, fs);
+        }
+        let proc = self.ensure(leader);
+        if !thread.execs.is_empty() {
+            proc.exec = thread.exec;
+        }
+        proc.execs.extend(thread.execs);
+        proc.signals.extend(thread.signals);
+        proc.children.extend(thread.children);
+    }
+
+    /// `pid` ended; its working directory is freed once no other task
+    /// shares it.
+    pub fn exited(&mut self, pid: i32, status: ExitStatus) {
+        let proc = self.ensure(pid);
+        proc.exit = Some(status);
+        let fs = proc.fs.take();
+        self.release(fs);
+    }
+
+    pub fn signal(&mut self, pid: i32, signal: Signal) {
+        self.ensure(pid).signals.push(signal);
+    }
+
+    /// `unshare(CLONE_FS)`: `pid` keeps its working directory but no
+    /// longer shares later changes with the rest of its group.
+    pub fn unshare_fs(&mut self, pid: i32) {
+        let shared = self.fs(pid);
+        if self.cwds[shared].users > 1 {
+            let fs = self.new_fs(self.cwds[shared].cwd.clone());
+            self.set_fs(pid, fs);
+        }
+    }
+
+    /// Working directory of `pid`; the initial one if it is new or gone.
+    pub fn cwd(&self, pid: i32) -> &str {
+        match self.procs.get(&pid).and_then(|p| p.fs) {
+            Some(fs) => &self.cwds[fs].cwd,
+            None => &self.initial_cwd,
+        }
+    }
+
+    /// Change the working directory of `pid` and of every task sharing fs
+    /// state with it.
+    pub fn set_cwd(&mut self, pid: i32, path: &str) {
+        let fs = self.fs(pid);
+        self.ensure(pid).own_cwd = true;
+        self.cwds[fs].cwd = path.to_string();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_line;
+    use crate::parse::tests::accesses;
+    use crate::Context;
+
+    /// The context after parsing every line of `trace`.
+    fn parse(trace: &str) -> Context {
+        let mut ctx = Context::new();
+        ctx.set_dir("/w", None);
+        for l in trace.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            parse_line(l, &mut ctx).unwrap();
+        }
+        ctx
+    }
+
+    /// Slots some live task still points at.
+    fn used_slots(tree: &ProcessTree) -> usize {
+        tree.cwds.len() - tree.free.len()
+    }
+
+    #[test]
+    fn fs_slots_are_freed_and_reused() {
+        let mut tree = ProcessTree::new();
+        tree.set_initial_cwd("/w");
+        tree.add(1);
+        for child in 2..100 {
+            tree.add_early_child(1, child);
+            tree.clone_process(1, child, CloneFlags::empty());
+            tree.unshare_fs(child);
+            tree.exited(child, ExitStatus::exited(0));
+        }
+        assert_eq!(used_slots(&tree), 1);
+        assert!(tree.cwds.len() <= 3, "{} slots for one live task", tree.cwds.len());
+        assert_eq!(tree.cwd(1), "/w");
+    }
+
+    #[test]
+    fn shared_fs_outlives_one_of_its_users() {
+        let mut tree = ProcessTree::new();
+        tree.set_initial_cwd("/w");
+        tree.add(1);
+        tree.clone_process(1, 2, CloneFlags::CLONE_FS);
+        tree.exited(1, ExitStatus::exited(0));
+        tree.set_cwd(2, "/x");
+        tree.clone_process(2, 3, CloneFlags::empty());
+        assert_eq!(tree.cwd(3), "/x");
+        assert_eq!(used_slots(&tree), 2);
+    }
+
+    #[test]
+    fn clone_fs_chains_share_a_cwd_until_unshared() {
+        let trace = r#"
+            1 clone(child_stack=NULL, flags=CLONE_FS|SIGCHLD) = 2
+            2 clone(child_stack=NULL, flags=CLONE_FS|SIGCHLD) = 3
+            3 chdir("/x") = 0
+            1 stat("a", {st_mode=S_IFREG|0644, ...}) = 0
+            2 unshare(CLONE_FS) = 0
+            2 chdir("/y") = 0
+            1 stat("b", {st_mode=S_IFREG|0644, ...}) = 0
+            3 stat("c", {st_mode=S_IFREG|0644, ...}) = 0
+            2 stat("d", {st_mode=S_IFREG|0644, ...}) = 0
+        "#;
+        assert_eq!(accesses(trace), ["r /x", "r /x/a", "r /y", "r /x/b", "r /x/c", "r /y/d"]);
+        assert_eq!(parse(trace).process_tree().subtree(1), [1, 2, 3]);
+    }
+
+    #[test]
+    fn early_child_keeps_its_own_cwd_and_fds() {
+        let trace = r#"
+            100 chdir("/work") = 0
+            100 openat(AT_FDCWD, "log", O_WRONLY|O_CREAT, 0644) = 3
+            100 clone(child_stack=NULL, flags=CLONE_CHILD_CLEARTID|CLONE_CHILD_SETTID|SIGCHLD <unfinished ...>
+            101 openat(AT_FDCWD, "in.txt", O_RDONLY) = 4
+            101 chdir("sub") = 0
+            100 <... clone resumed>, child_tidptr=0x7f) = 101
+            101 write(3, "a", 1) = 1
+            101 read(4, "", 10) = 0
+            101 stat("y", {st_mode=S_IFREG|0644, ...}) = 0
+            100 stat("z", {st_mode=S_IFREG|0644, ...}) = 0
+        "#;
+        let expected = [
+            "r /work", "w /work/log",
+            "r /work/in.txt", "r /work/sub",
+            "w /work/log", "r /work/in.txt", "r /work/sub/y",
+            "r /work/z",
+        ];
+        assert_eq!(accesses(trace), expected);
+        assert_eq!(parse(trace).process_tree().get(101).and_then(|p| p.parent), Some(100));
+    }
+
+    #[test]
+    fn thread_exec_takes_over_the_leader_mid_call() {
+        let trace = r#"
+            200 execve("/bin/sh", ["sh"], 0x7ffe /* 3 vars */) = 0
+            200 clone(child_stack=0x7f, flags=CLONE_VM|CLONE_FS|CLONE_FILES|CLONE_SIGHAND|CLONE_THREAD, tls=0x7f) = 201
+            200 futex(0x7f, FUTEX_WAIT, 2, NULL <unfinished ...>
+            201 openat(AT_FDCWD, "/w/data", O_RDONLY) = 3
+            201 execve("/bin/true", ["true"], 0x7ffe /* 3 vars */ <unfinished ...>
+            200 <... futex resumed>) = ?
+            200 +++ superseded by execve in pid 201 +++
+            200 <... execve resumed>) = 0
+            200 read(3, "x", 1) = 1
+            200 +++ exited with 0 +++
+        "#;
+        assert_eq!(accesses(trace), ["r /bin/sh", "r /w/data", "r /bin/true", "r /w/data"]);
+        let ctx = parse(trace);
+        let tree = ctx.process_tree();
+        assert!(tree.get(201).is_none());
+        let leader = tree.get(200).unwrap();
+        assert_eq!(leader.execs.iter().map(|e| e.exe.as_str()).collect::<Vec<_>>(), ["/bin/sh", "/bin/true"]);
+        assert_eq!(leader.exit, Some(ExitStatus::exited(0)));
+        assert!(leader.children.is_empty());
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::context::Context;
+use crate::error::ParseError;
+use crate::event::Access;
+use crate::exclude::{PathRules, Suppressed};
+use crate::files::RwFile;
+use crate::parse::{handle_info, parse_line, LineResult, strip_pid};
+use crate::process::{Exec, ProcessTree};
+
+/// Exit code of the first traced pid, taken from its `+++ exited with N +++`
+/// line. `None` if the trace is empty or that pid never exits.
+pub fn parse_exit_code<S: AsRef<str>>(trace_object: &[S]) -> Option<i32>{
+    if trace_object.is_empty() || trace_object[0].as_ref().is_empty() {
+        return None
+    };
+
+    let l = trace_object[0].as_ref();
+    let (first_pid, _) = strip_pid(l).ok()?;
+    for l in trace_object {
+        let Ok((pid, l)) = strip_pid(l.as_ref()) else {
+            continue;
+        };
+        if let Ok((true, Some(info))) = handle_info(l) && pid == first_pid {
+            return Some(info.exitcode);
+        }
+    };
+    None
+}
+
+/// Read and write sets of a whole trace. Bad lines are handled according to
+/// the context's [`crate::ErrorMode`].
+pub fn parse_and_gather_cmd_rw_sets<S: AsRef<str>>(trace_object: &[S], ctx: &mut Context) -> Result<(HashSet<String>, HashSet<String>), ParseError> {
+    let mut records: Vec<RwFile> = Vec::new();
+
+    for l in trace_object {
+        match parse_line(l.as_ref(), ctx) {
+            Ok(LineResult::Files(files)) => records.extend(files),
+            Ok(_) => {}
+            Err(e) => ctx.report(e)?,
+        };
+    }
+
+    Ok(gather_rw_sets(records))
+}
+
+/// Fold already-parsed records into read and write sets: drops the paths
+/// [`PathRules::defaults`] excludes and adds every parent directory of
+/// each path.
+pub fn gather_rw_sets<I: IntoIterator<Item = RwFile>>(records: I) -> (HashSet<String>, HashSet<String>) {
+    let mut sets = RwSetBuilder::new();
+    for record in records {
+        sets.add(record);
+    }
+    sets.into_sets()
+}
+
+/// Bytes a trace moved in and out of one file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct ByteCount {
+    pub read: u64,
+    pub written: u64,
+}
+
+/// Read and write sets of one pid or process subtree, sorted.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct PidSets {
+    pub read: BTreeSet<String>,
+    pub write: BTreeSet<String>,
+}
+
+impl PidSets {
+    pub fn is_empty(&self) -> bool {
+        self.read.is_empty() && self.write.is_empty()
+    }
+
+    fn extend(&mut self, other: &PidSets) {
+        self.read.extend(other.read.iter().cloned());
+        self.write.extend(other.write.iter().cloned());
+    }
+
+    fn insert_all(&mut self, write: bool, paths: &[String]) {
+        let set = if write { &mut self.write } else { &mut self.read };
+        set.extend(paths.iter().cloned());
+    }
+}
+
+/// Sets of one run of a program: what `pid` did between one exec and the
+/// next (or its exit).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CommandSets {
+    pub pid: i32,
+    /// `None` if the trace never showed what the process runs.
+    pub exec: Option<Exec>,
+    #[serde(flatten)]
+    pub sets: PidSets,
+}
+
+/// Who a record is attributed to besides the whole trace.
+enum Owner<'a> {
+    Trace,
+    Pid(i32),
+    Command(i32, Option<&'a Exec>),
+}
+
+/// Read and write sets built up one record at a time, so a trace never has
+/// to be held in memory. Applies the same rules as [`gather_rw_sets`].
+#[derive(Debug, Clone, Default)]
+pub struct RwSetBuilder {
+    read_set: HashSet<String>,
+    write_set: HashSet<String>,
+    bytes: HashMap<String, ByteCount>,
+    written_only: bool,
+    rules: PathRules,
+    /// Sets of each pid, if asked for with [`RwSetBuilder::per_pid`].
+    by_pid: Option<HashMap<i32, PidSets>>,
+    /// Sets of each command, if asked for with [`RwSetBuilder::per_command`].
+    commands: Option<Vec<CommandSets>>,
+    /// Index into `commands` of the run each pid is in.
+    current_command: HashMap<i32, usize>,
+}
+
+impl RwSetBuilder {
+    pub fn new() -> RwSetBuilder {
+        RwSetBuilder::default()
+    }
+
+    /// Only put a file in the write set if something actually changed it:
+    /// bytes written, a truncating or exclusive-create open, or a metadata
+    /// call such as `unlink` or `rename`. Opening for writing alone is not
+    /// enough.
+    pub fn written_only(mut self, on: bool) -> Self {
+        self.written_only = on;
+        self
+    }
+
+    /// Also keep the sets of each pid, filled by
+    /// [`RwSetBuilder::add_for_pid`].
+    pub fn per_pid(mut self, on: bool) -> Self {
+        self.by_pid = on.then(HashMap::new);
+        self
+    }
+
+    /// Also keep the sets of each command run, filled by
+    /// [`RwSetBuilder::add_for_command`].
+    pub fn per_command(mut self, on: bool) -> Self {
+        self.commands = on.then(Vec::new);
+        self
+    }
+
+    /// Replace the default exclusion rules.
+    pub fn path_rules(mut self, rules: PathRules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    fn counts_as_write(&self, record: &RwFile) -> bool {
+        if !self.written_only {
+            return true;
+        }
+        match (record.intent(), record.bytes()) {
+            (Some(i), _) => i.trunc || (i.create && i.excl),
+            (None, Some(n)) => n > 0,
+            (None, None) => true,
+        }
+    }
+
+    pub fn add(&mut self, record: RwFile) {
+        self.add_inner(Owner::Trace, record);
+    }
+
+    /// Add a record made by `pid`, to the whole-trace sets and, with
+    /// [`RwSetBuilder::per_pid`], to that pid's.
+    pub fn add_for_pid(&mut self, pid: i32, record: RwFile) {
+        self.add_inner(Owner::Pid(pid), record);
+    }
+
+    /// Like [`RwSetBuilder::add_for_pid`], where `pid` is running `exec`
+    /// (see [`crate::Process::exec`]). A new run starts whenever a pid's
+    /// `exec` changes.
+    pub fn add_for_command(&mut self, pid: i32, exec: Option<&Exec>, record: RwFile) {
+        self.add_inner(Owner::Command(pid, exec), record);
+    }
+
+    fn command_index(&mut self, pid: i32, exec: Option<&Exec>) -> Option<usize> {
+        let commands = self.commands.as_mut()?;
+        if let Some(&i) = self.current_command.get(&pid)
+            && commands[i].exec.as_ref() == exec
+        {
+            return Some(i);
+        }
+        commands.push(CommandSets { pid, exec: exec.cloned(), sets: PidSets::default() });
+        self.current_command.insert(pid, commands.len() - 1);
+        Some(commands.len() - 1)
+    }
+
+    fn add_inner(&mut self, owner: Owner, record: RwFile) {
+        let access = match record {
+            RwFile::Read(_) => Access::Read,
+            RwFile::Write(_) => Access::Write,
+        };
+        if !self.rules.allows(record.fname(), access) {
+            return;
+        }
+        if let Some(n) = record.bytes() {
+            let count = self.bytes.entry(record.fname().to_string()).or_default();
+            match record {
+                RwFile::Read(_) => count.read += n,
+                RwFile::Write(_) => count.written += n,
+            }
+        }
+        if let RwFile::Write(_) = record && !self.counts_as_write(&record) {
+            return;
+        }
+
+        let (write, paths): (bool, Vec<String>) = match record {
+            RwFile::Read(rf) => (false, rf.closure().into_iter().map(|r| r.fname).collect()),
+            RwFile::Write(wf) => (true, wf.closure().into_iter().map(|w| w.fname).collect()),
+        };
+        let pid = match owner {
+            Owner::Trace => None,
+            Owner::Pid(pid) => Some(pid),
+            Owner::Command(pid, exec) => {
+                if let Some(i) = self.command_index(pid, exec)
+                    && let Some(commands) = self.commands.as_mut()
+                {
+                    commands[i].sets.insert_all(write, &paths);
+                }
+                Some(pid)
+            }
+        };
+        if let (Some(pid), Some(by_pid)) = (pid, self.by_pid.as_mut()) {
+            by_pid.entry(pid).or_default().insert_all(write, &paths);
+        }
+        let set = if write { &mut self.write_set } else { &mut self.read_set };
+        set.extend(paths);
+    }
+
+    pub fn read_set(&self) -> &HashSet<String> {
+        &self.read_set
+    }
+
+    pub fn write_set(&self) -> &HashSet<String> {
+        &self.write_set
+    }
+
+    /// Per-file byte counts from data calls.
+    pub fn byte_counts(&self) -> &HashMap<String, ByteCount> {
+        &self.bytes
+    }
+
+    /// Sets of `pid`'s own accesses; `None` if it made none or
+    /// [`RwSetBuilder::per_pid`] is off.
+    pub fn pid_sets(&self, pid: i32) -> Option<&PidSets> {
+        self.by_pid.as_ref()?.get(&pid)
+    }
+
+    /// Pids with sets of their own, in ascending order.
+    pub fn pids(&self) -> Vec<i32> {
+        let mut pids: Vec<i32> = self.by_pid.iter().flat_map(|m| m.keys().copied()).collect();
+        pids.sort_unstable();
+        pids
+    }
+
+    /// Union of the sets of `pid` and every process and thread under it in
+    /// `tree`.
+    pub fn subtree_sets(&self, tree: &ProcessTree, pid: i32) -> PidSets {
+        let mut sets = PidSets::default();
+        for p in tree.subtree(pid) {
+            if let Some(own) = self.pid_sets(p) {
+                sets.extend(own);
+            }
+        }
+        sets
+    }
+
+    /// Sets of each command run, in the order the runs started; empty
+    /// unless [`RwSetBuilder::per_command`] is on.
+    pub fn commands(&self) -> &[CommandSets] {
+        self.commands.as_deref().unwrap_or_default()
+    }
+
+    /// Accesses each exclude rule kept out of the sets.
+    pub fn suppressed(&self) -> Vec<Suppressed> {
+        self.rules.suppressed()
+    }
+
+    pub fn into_sets(self) -> (HashSet<String>, HashSet<String>) {
+        (self.read_set, self.write_set)
+    }
+}
@@ -0,0 +1,141 @@
+//! Run a command under the real `strace` and read its log as it is written.
+//!
+//! strace writes into a FIFO rather than a file, so lines can be parsed while
+//! the command is still running and nothing is left on disk afterwards.
+
+use std::ffi::{CString, OsStr};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Lines};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus};
+use std::sync::mpsc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// Default `-s`: long enough that paths are never truncated.
+pub const DEFAULT_STRING_LIMIT: usize = 4096;
+
+/// The flags the parser relies on: `-f` to follow children (and prefix each
+/// line with its pid), `-y` for `<path>` annotations on fds, and `-s` so
+/// path strings are printed in full.
+pub fn strace_args(fifo: &Path, string_limit: usize) -> Vec<String> {
+    vec![
+        "-f".to_string(),
+        "-y".to_string(),
+        "-s".to_string(),
+        string_limit.to_string(),
+        "-o".to_string(),
+        fifo.to_string_lossy().into_owned(),
+    ]
+}
+
+fn make_fifo_dir() -> io::Result<PathBuf> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let dir = std::env::temp_dir().join(format!("strace-parse-{}-{}", std::process::id(), n));
+    fs::create_dir(&dir)?;
+    Ok(dir)
+}
+
+fn mkfifo(path: &Path) -> io::Result<()> {
+    let c = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: `c` is a valid NUL-terminated path.
+    if unsafe { libc::mkfifo(c.as_ptr(), 0o600) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// A running `strace -f -y -s N -o <fifo> -- <program>`.
+pub struct Strace {
+    child: Child,
+    lines: Lines<BufReader<File>>,
+    dir: PathBuf,
+}
+
+impl Strace {
+    /// Start `program` under the strace binary `strace`.
+    pub fn spawn<S: AsRef<OsStr>>(strace: &OsStr, program: &[S], string_limit: usize) -> io::Result<Strace> {
+        let dir = make_fifo_dir()?;
+        let fifo = dir.join("trace");
+        let result = mkfifo(&fifo).and_then(|_| {
+            let child = Command::new(strace)
+                .args(strace_args(&fifo, string_limit))
+                .arg("--")
+                .args(program)
+                .spawn()?;
+            Self::open_fifo(&fifo, child)
+        });
+        match result {
+            Ok((child, file)) => Ok(Strace {
+                child,
+                lines: BufReader::new(file).lines(),
+                dir,
+            }),
+            Err(e) => {
+                let _ = fs::remove_dir_all(&dir);
+                Err(e)
+            }
+        }
+    }
+
+    /// Opening a FIFO blocks until strace opens the other end; if strace
+    /// dies first, open it ourselves so the reader sees EOF instead.
+    fn open_fifo(fifo: &Path, mut child: Child) -> io::Result<(Child, File)> {
+        let (tx, rx) = mpsc::channel();
+        let path = fifo.to_path_buf();
+        thread::spawn(move || tx.send(File::open(path)));
+        loop {
+            match rx.recv_timeout(Duration::from_millis(50)) {
+                Ok(file) => return Ok((child, file?)),
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    if let Some(status) = child.try_wait()? {
+                        let _ = OpenOptions::new()
+                            .write(true)
+                            .custom_flags(libc::O_NONBLOCK)
+                            .open(fifo);
+                        let _ = rx.recv();
+                        return Err(io::Error::other(format!("strace exited before tracing started ({status})")));
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::other("fifo reader thread died"));
+                }
+            }
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Stop strace, e.g. when we give up reading its log: with nobody
+    /// draining the FIFO it would block forever once the pipe fills. The
+    /// traced command is detached and keeps running.
+    pub fn kill(&mut self) -> io::Result<()> {
+        self.child.kill()
+    }
+
+    /// Wait for strace to exit. It exits with the traced command's status.
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        self.child.wait()
+    }
+}
+
+impl Iterator for Strace {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.lines.next()
+    }
+}
+
+impl Drop for Strace {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, Lines};
+
+use crate::audit::SyscallAudit;
+use crate::context::Context;
+use crate::error::StreamError;
+use crate::event::AccessEvent;
+use crate::parse::{parse_record, Record};
+use crate::rwset::RwSetBuilder;
+
+/// Access events read lazily from any [`BufRead`], e.g. a pipe from strace
+/// or `io::stdin().lock()`.
+///
+/// Lines are not kept once parsed. What stays in memory grows with the
+/// number of processes and files rather than lines: `<unfinished ...>` calls
+/// still waiting for their `resumed>` half, the read/write sets, and the
+/// [`Context`]'s process tree with every pid seen, each process's working
+/// directory and open fds. Bad lines follow the context's [`crate::ErrorMode`]: lenient
+/// streams record the first [`crate::MAX_DIAGNOSTICS`] in
+/// [`Context::diagnostics`] and count the rest, strict ones yield the error
+/// and then end.
+pub struct EventStream<R> {
+    lines: Lines<R>,
+    ctx: Context,
+    queue: VecDeque<AccessEvent>,
+    sets: RwSetBuilder,
+    unknown: SyscallAudit,
+    first_pid: Option<i32>,
+    exit_code: Option<i32>,
+    done: bool,
+}
+
+impl<R: BufRead> EventStream<R> {
+    pub fn new(reader: R, ctx: Context) -> EventStream<R> {
+        EventStream {
+            lines: reader.lines(),
+            ctx,
+            queue: VecDeque::new(),
+            sets: RwSetBuilder::new(),
+            unknown: SyscallAudit::new(),
+            first_pid: None,
+            exit_code: None,
+            done: false,
+        }
+    }
+
+    /// Also keep read and write sets per command run; see
+    /// [`RwSetBuilder::per_command`].
+    pub fn per_command(mut self, on: bool) -> EventStream<R> {
+        self.sets = self.sets.per_command(on);
+        self
+    }
+
+    /// Also keep read and write sets per pid; see [`RwSetBuilder::per_pid`].
+    /// Subtree sets need the finished context's
+    /// [`Context::process_tree`].
+    pub fn per_pid(mut self, on: bool) -> EventStream<R> {
+        self.sets = self.sets.per_pid(on);
+        self
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.ctx
+    }
+
+    /// Read and write sets of everything yielded so far.
+    pub fn rw_sets(&self) -> &RwSetBuilder {
+        &self.sets
+    }
+
+    /// Unclassified syscalls seen so far.
+    pub fn unknown_syscalls(&self) -> &SyscallAudit {
+        &self.unknown
+    }
+
+    /// Exit code of the first pid in the trace, once its `+++` line is read.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    /// Drain the stream and return its context and final sets.
+    pub fn finish(mut self) -> Result<(Context, RwSetBuilder), StreamError> {
+        for e in self.by_ref() {
+            e?;
+        }
+        Ok((self.ctx, self.sets))
+    }
+
+    fn read_line(&mut self) -> Result<bool, StreamError> {
+        let Some(line) = self.lines.next() else {
+            return Ok(false);
+        };
+        let line = line?;
+        let record = match parse_record(&line, &mut self.ctx) {
+            Ok(record) => record,
+            Err(e) => {
+                self.ctx.report(e)?;
+                return Ok(true);
+            }
+        };
+        match record {
+            Record::Info { pid, status } => {
+                let first = *self.first_pid.get_or_insert(pid);
+                if let Some(status) = status && pid == first && self.exit_code.is_none() {
+                    self.exit_code = Some(status.exitcode);
+                }
+            }
+            Record::Signal { pid, .. } => {
+                self.first_pid.get_or_insert(pid);
+            }
+            Record::Call { call, files } => {
+                self.first_pid.get_or_insert(call.pid);
+                if files.is_none() {
+                    self.unknown.add(&call);
+                }
+                for e in AccessEvent::from_call(&call, files.unwrap_or_default()) {
+                    let exec = self.ctx.process_tree().get(e.pid).and_then(|p| p.exec.as_ref());
+                    self.sets.add_for_command(e.pid, exec, e.to_rwfile());
+                    self.queue.push_back(e);
+                }
+            }
+            Record::Skip => {}
+        }
+        Ok(true)
+    }
+}
+
+impl<R: BufRead> Iterator for EventStream<R> {
+    type Item = Result<AccessEvent, StreamError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(e) = self.queue.pop_front() {
+                return Some(Ok(e));
+            }
+            if self.done {
+                return None;
+            }
+            match self.read_line() {
+                Ok(true) => {}
+                Ok(false) => self.done = true,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,223 @@
+//! Site-specific syscall classification, merged over the built-in tables
+//! ([`crate::R_FIRST_PATH_SET`], [`crate::IGNORE_SET`], ...).
+//!
+//! A table file lists syscalls by name, each with the shape of its
+//! arguments and what it does to the file they name. In TOML:
+//!
+//! ```toml
+//! [syscalls]
+//! access = { layout = "first_path", access = "none" }
+//! my_open = { layout = "open" }
+//! getrandom = { layout = "ignore" }
+//! ```
+//!
+//! or the same as JSON, `{"syscalls": {"access": {"layout": "first_path",
+//! "access": "none"}, ...}}`. An entry replaces whatever the built-in
+//! tables say about that syscall.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::error::TableError;
+
+/// Where a syscall's path arguments are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Layout {
+    /// `name(path, ...)`, like `stat`.
+    FirstPath,
+    /// `name(dirfd, path, ...)`, like `newfstatat`.
+    FdPath,
+    /// `name(path, path, ...)`, like `rename`; both paths get the same access.
+    TwoPaths,
+    /// `name(path, flags, ...)`, classified by its open flags like `open`.
+    Open,
+    /// `name(dirfd, path, flags, ...)`, classified like `openat`.
+    Openat,
+    /// Touches no files.
+    Ignore,
+}
+
+/// What a path-taking syscall does to its file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Semantics {
+    Read,
+    /// A write; a failed call is only a read, as for the built-in writes.
+    Write,
+    /// Looks at metadata only; the path is resolved but nothing is recorded.
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub layout: Layout,
+    /// Required for the path layouts; the open layouts take it from the
+    /// flags and `ignore` has none.
+    #[serde(default)]
+    pub access: Option<Semantics>,
+}
+
+impl Rule {
+    fn check(&self, name: &str) -> Result<(), TableError> {
+        match (self.layout, self.access) {
+            (Layout::FirstPath | Layout::FdPath | Layout::TwoPaths, None) => {
+                Err(TableError::Invalid(format!("`{name}` needs an access (read, write or none)")))
+            }
+            (Layout::Open | Layout::Openat | Layout::Ignore, Some(_)) => {
+                Err(TableError::Invalid(format!("`{name}` cannot set an access for this layout")))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TableFile {
+    #[serde(default)]
+    syscalls: HashMap<String, Rule>,
+}
+
+/// Overrides for the built-in classification, by syscall name.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SyscallTable {
+    rules: HashMap<String, Rule>,
+}
+
+impl SyscallTable {
+    pub fn new() -> SyscallTable {
+        SyscallTable::default()
+    }
+
+    fn from_file(file: TableFile) -> Result<SyscallTable, TableError> {
+        for (name, rule) in &file.syscalls {
+            rule.check(name)?;
+        }
+        Ok(SyscallTable { rules: file.syscalls })
+    }
+
+    pub fn from_json(s: &str) -> Result<SyscallTable, TableError> {
+        SyscallTable::from_file(serde_json::from_str(s)?)
+    }
+
+    pub fn from_toml(s: &str) -> Result<SyscallTable, TableError> {
+        SyscallTable::from_file(toml::from_str(s)?)
+    }
+
+    /// Read a table file: JSON if it ends in `.json`, TOML otherwise.
+    pub fn load(path: &Path) -> Result<SyscallTable, TableError> {
+        let s = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => SyscallTable::from_json(&s),
+            _ => SyscallTable::from_toml(&s),
+        }
+    }
+
+    pub fn insert(&mut self, name: &str, rule: Rule) -> Result<(), TableError> {
+        rule.check(name)?;
+        self.rules.insert(name.to_string(), rule);
+        Ok(())
+    }
+
+    /// Add `other`'s rules, replacing ours where both name a syscall.
+    pub fn merge(&mut self, other: SyscallTable) {
+        self.rules.extend(other.rules);
+    }
+
+    pub fn get(&self, syscall: &str) -> Option<&Rule> {
+        self.rules.get(syscall)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LineResult, RwFile, TraceParser};
+
+    /// `r path` or `w path` for each file `line` touches under `table`.
+    fn classify(table: &SyscallTable, line: &str) -> Vec<String> {
+        let mut parser = TraceParser::with_cwd("/w").syscall_table(table.clone());
+        match parser.parse_line(line).unwrap() {
+            LineResult::Files(files) => files
+                .iter()
+                .map(|f| match f {
+                    RwFile::Read(r) => format!("r {}", r.fname),
+                    RwFile::Write(w) => format!("w {}", w.fname),
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn toml_table_overrides_the_builtin_sets() {
+        let table = SyscallTable::from_toml(
+            r#"
+            [syscalls]
+            access = { layout = "first_path", access = "none" }
+            my_open = { layout = "open" }
+            "#,
+        )
+        .unwrap();
+        let access = r#"1 access("/etc/ld.so.preload", R_OK) = -1 ENOENT (No such file or directory)"#;
+        assert_eq!(classify(&SyscallTable::new(), access), ["r /etc/ld.so.preload"]);
+        assert_eq!(classify(&table, access), Vec::<String>::new());
+        assert_eq!(classify(&table, r#"1 my_open("out", O_WRONLY|O_CREAT, 0644) = 3"#), ["w /w/out"]);
+    }
+
+    #[test]
+    fn json_table_overrides_the_builtin_sets() {
+        let table = SyscallTable::from_json(
+            r#"{"syscalls": {"stat": {"layout": "first_path", "access": "write"}, "getrandom": {"layout": "ignore"}}}"#,
+        )
+        .unwrap();
+        let stat = r#"1 stat("f", {st_mode=S_IFREG|0644, ...}) = 0"#;
+        assert_eq!(classify(&SyscallTable::new(), stat), ["r /w/f"]);
+        assert_eq!(classify(&table, stat), ["w /w/f"]);
+        assert_eq!(table.get("getrandom").map(|r| r.layout), Some(Layout::Ignore));
+    }
+
+    #[test]
+    fn unknown_layout_is_a_parse_error() {
+        let err = SyscallTable::from_toml("[syscalls]\nstat = { layout = \"third_path\", access = \"read\" }\n").unwrap_err();
+        assert!(matches!(err, TableError::Toml(_)), "{err:?}");
+        assert!(err.to_string().contains("third_path"), "{err}");
+    }
+
+    #[test]
+    fn access_that_does_not_fit_the_layout_is_invalid() {
+        let missing = SyscallTable::from_json(r#"{"syscalls": {"stat": {"layout": "first_path"}}}"#).unwrap_err();
+        assert!(matches!(&missing, TableError::Invalid(s) if s.contains("`stat`")), "{missing:?}");
+        let extra = SyscallTable::from_json(r#"{"syscalls": {"my_open": {"layout": "open", "access": "read"}}}"#).unwrap_err();
+        assert!(matches!(extra, TableError::Invalid(_)), "{extra:?}");
+    }
+
+    #[test]
+    fn parse_errors_say_where() {
+        let TableError::Json(err) = SyscallTable::from_json("{\"syscalls\": {\n  \"stat\": }}").unwrap_err() else {
+            panic!("expected a JSON error");
+        };
+        assert_eq!((err.line(), err.column()), (2, 11));
+
+        let err = SyscallTable::from_toml("[syscalls]\nstat = {\n").unwrap_err();
+        let TableError::Toml(toml) = &err else {
+            panic!("expected a TOML error: {err:?}");
+        };
+        assert!(toml.span().is_some());
+        assert!(err.to_string().contains("line 2"), "{err}");
+    }
+
+    #[test]
+    fn missing_file_is_an_io_error() {
+        let err = SyscallTable::load(Path::new("/nonexistent/table.toml")).unwrap_err();
+        assert!(matches!(err, TableError::Io(_)), "{err:?}");
+    }
+}
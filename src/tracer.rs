@@ -0,0 +1,1058 @@
+//! Built-in ptrace tracer, so a command can be traced without strace.
+//!
+//! Each path-bearing syscall's arguments are decoded straight into the
+//! [`Arg`] values the log parser builds from `strace -f -y` output (paths,
+//! `fd</path>` annotations, symbolic flags) and handed to the same
+//! handlers, so both routes produce identical [`crate::RwFile`] records
+//! without a path ever being printed and read back.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+use crate::args::{Arg, FdNum};
+use crate::flags::{bit_parts, CloneFlags, MapFlags, ProtFlags, ResolveFlags};
+use crate::parse::{ExitStatus, Signal, SyscallLine};
+
+/// Not exported by every libc target, but stable since Linux 5.3.
+const PTRACE_GET_SYSCALL_INFO: libc::c_uint = 0x420e;
+const PTRACE_SYSCALL_INFO_ENTRY: u8 = 1;
+const PTRACE_SYSCALL_INFO_EXIT: u8 = 2;
+
+/// Longest `argv` rendered before it is cut short with `...`.
+const MAX_ARGV: u64 = 4096;
+
+/// `struct ptrace_syscall_info`; `data` is the entry/exit/seccomp union.
+#[repr(C)]
+#[derive(Default)]
+#[allow(dead_code)]
+struct SyscallInfo {
+    op: u8,
+    reserved: u8,
+    flags: u16,
+    arch: u32,
+    instruction_pointer: u64,
+    stack_pointer: u64,
+    data: [u64; 9],
+}
+
+/// How a syscall's arguments map onto the handlers in `parse_syscall`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Layout {
+    /// `path, ...`
+    Path,
+    /// `path, flags, ...` (open)
+    PathFlags,
+    /// `path, argv, envp` (execve)
+    Exec,
+    /// `dirfd, path, argv, envp, flags` (execveat)
+    FdPathExec,
+    /// `path, path`
+    TwoPaths,
+    /// `target, dirfd, path` (symlinkat)
+    PathFdPath,
+    /// `dirfd, path, ...`; the rest are passed as raw numbers so the
+    /// parser can find `AT_*` flags wherever the call keeps them.
+    FdPath,
+    /// `dirfd, path, flags, ...` (openat)
+    FdPathFlags,
+    /// `dirfd, path, struct open_how *, size` (openat2)
+    FdPathHow,
+    /// `dirfd, path, dirfd, path, flags`
+    FdPathFdPath,
+    /// `flags, ...` (clone)
+    Clone,
+    /// `struct clone_args *, size`
+    Clone3,
+    /// `flags` (unshare)
+    Unshare,
+    /// No arguments (fork, vfork)
+    NoArgs,
+    /// `fd, buf, count` (read, write, ftruncate, ...)
+    Fd,
+    /// `fd, fd, ...` (sendfile, tee)
+    FdFd,
+    /// `fd, off, fd, ...` (splice, copy_file_range)
+    FdOffFd,
+    /// `addr, length, prot, flags, fd, offset`
+    Mmap,
+    /// `source, target, fstype, flags, data`
+    Mount,
+    /// `fd, flags, mask, dirfd, path` (fanotify_mark)
+    FanotifyMark,
+    /// `mount_fd, handle, flags` (open_by_handle_at)
+    Handle,
+    /// `cmd, special, id, addr` (quotactl)
+    Quotactl,
+    /// `fd, cmd, key, value, aux` (fsconfig)
+    Fsconfig,
+    /// `fd, struct sockaddr *, addrlen` (bind, connect)
+    Sockaddr,
+}
+
+/// Syscall numbers, with the ones newer than the libc we build against.
+/// Syscalls added since Linux 5.x share one number on every architecture.
+#[allow(non_upper_case_globals)]
+mod sys {
+    pub use libc::*;
+
+    pub const SYS_fchmodat2: c_long = 452;
+    pub const SYS_setxattrat: c_long = 463;
+    pub const SYS_getxattrat: c_long = 464;
+    pub const SYS_listxattrat: c_long = 465;
+    pub const SYS_removexattrat: c_long = 466;
+}
+
+macro_rules! syscalls {
+    ($($(#[$m:meta])* $nr:ident => $name:literal, $layout:ident;)*) => {
+        fn lookup(nr: u64) -> Option<(&'static str, Layout)> {
+            $(
+                $(#[$m])*
+                if nr == sys::$nr as u64 {
+                    return Some(($name, Layout::$layout));
+                }
+            )*
+            None
+        }
+    };
+}
+
+syscalls! {
+    SYS_execve => "execve", Exec;
+    SYS_statfs => "statfs", Path;
+    SYS_getxattr => "getxattr", Path;
+    SYS_lgetxattr => "lgetxattr", Path;
+    SYS_llistxattr => "llistxattr", Path;
+    SYS_truncate => "truncate", Path;
+    SYS_acct => "acct", Path;
+    SYS_setxattr => "setxattr", Path;
+    SYS_removexattr => "removexattr", Path;
+    SYS_chdir => "chdir", Path;
+    SYS_newfstatat => "newfstatat", FdPath;
+    SYS_statx => "statx", FdPath;
+    SYS_name_to_handle_at => "name_to_handle_at", FdPath;
+    SYS_readlinkat => "readlinkat", FdPath;
+    SYS_faccessat => "faccessat", FdPath;
+    SYS_execveat => "execveat", FdPathExec;
+    SYS_faccessat2 => "faccessat2", FdPath;
+    SYS_unlinkat => "unlinkat", FdPath;
+    SYS_utimensat => "utimensat", FdPath;
+    SYS_mkdirat => "mkdirat", FdPath;
+    SYS_mknodat => "mknodat", FdPath;
+    SYS_fchownat => "fchownat", FdPath;
+    SYS_fchmodat => "fchmodat", FdPath;
+    SYS_fchmodat2 => "fchmodat2", FdPath;
+    SYS_inotify_add_watch => "inotify_add_watch", FdPath;
+    SYS_openat => "openat", FdPathFlags;
+    SYS_openat2 => "openat2", FdPathHow;
+    SYS_linkat => "linkat", FdPathFdPath;
+    // aarch64 only has renameat2.
+    #[cfg(target_arch = "x86_64")]
+    SYS_renameat => "renameat", FdPathFdPath;
+    SYS_renameat2 => "renameat2", FdPathFdPath;
+    SYS_symlinkat => "symlinkat", PathFdPath;
+    SYS_clone => "clone", Clone;
+    SYS_clone3 => "clone3", Clone3;
+    SYS_unshare => "unshare", Unshare;
+    SYS_read => "read", Fd;
+    SYS_readv => "readv", Fd;
+    SYS_pread64 => "pread64", Fd;
+    SYS_preadv => "preadv", Fd;
+    SYS_preadv2 => "preadv2", Fd;
+    SYS_write => "write", Fd;
+    SYS_writev => "writev", Fd;
+    SYS_pwrite64 => "pwrite64", Fd;
+    SYS_pwritev => "pwritev", Fd;
+    SYS_pwritev2 => "pwritev2", Fd;
+    SYS_ftruncate => "ftruncate", Fd;
+    SYS_fallocate => "fallocate", Fd;
+    SYS_sendfile => "sendfile", FdFd;
+    SYS_tee => "tee", FdFd;
+    SYS_splice => "splice", FdOffFd;
+    SYS_copy_file_range => "copy_file_range", FdOffFd;
+    SYS_mmap => "mmap", Mmap;
+    SYS_listxattr => "listxattr", Path;
+    SYS_lsetxattr => "lsetxattr", Path;
+    SYS_lremovexattr => "lremovexattr", Path;
+    SYS_chroot => "chroot", Path;
+    SYS_swapon => "swapon", Path;
+    SYS_swapoff => "swapoff", Path;
+    SYS_umount2 => "umount2", Path;
+    SYS_pivot_root => "pivot_root", TwoPaths;
+    SYS_fstat => "fstat", Fd;
+    SYS_fstatfs => "fstatfs", Fd;
+    SYS_fgetxattr => "fgetxattr", Fd;
+    SYS_flistxattr => "flistxattr", Fd;
+    SYS_getdents64 => "getdents64", Fd;
+    SYS_readahead => "readahead", Fd;
+    SYS_fchmod => "fchmod", Fd;
+    SYS_fchown => "fchown", Fd;
+    SYS_fsetxattr => "fsetxattr", Fd;
+    SYS_fremovexattr => "fremovexattr", Fd;
+    SYS_mount_setattr => "mount_setattr", FdPath;
+    SYS_open_tree => "open_tree", FdPath;
+    SYS_getxattrat => "getxattrat", FdPath;
+    SYS_listxattrat => "listxattrat", FdPath;
+    SYS_setxattrat => "setxattrat", FdPath;
+    SYS_removexattrat => "removexattrat", FdPath;
+    SYS_fchdir => "fchdir", Fd;
+    SYS_quotactl_fd => "quotactl_fd", Fd;
+    SYS_mount => "mount", Mount;
+    SYS_move_mount => "move_mount", FdPathFdPath;
+    SYS_fspick => "fspick", FdPath;
+    SYS_fsconfig => "fsconfig", Fsconfig;
+    SYS_fanotify_mark => "fanotify_mark", FanotifyMark;
+    SYS_open_by_handle_at => "open_by_handle_at", Handle;
+    SYS_quotactl => "quotactl", Quotactl;
+    SYS_bind => "bind", Sockaddr;
+    SYS_connect => "connect", Sockaddr;
+    #[cfg(target_arch = "x86_64")]
+    SYS_getdents => "getdents", Fd;
+    #[cfg(target_arch = "x86_64")]
+    SYS_futimesat => "futimesat", FdPath;
+    #[cfg(target_arch = "x86_64")]
+    SYS_uselib => "uselib", Path;
+    #[cfg(target_arch = "x86_64")]
+    SYS_stat => "stat", Path;
+    #[cfg(target_arch = "x86_64")]
+    SYS_lstat => "lstat", Path;
+    #[cfg(target_arch = "x86_64")]
+    SYS_access => "access", Path;
+    #[cfg(target_arch = "x86_64")]
+    SYS_readlink => "readlink", Path;
+    #[cfg(target_arch = "x86_64")]
+    SYS_mkdir => "mkdir", Path;
+    #[cfg(target_arch = "x86_64")]
+    SYS_rmdir => "rmdir", Path;
+    #[cfg(target_arch = "x86_64")]
+    SYS_creat => "creat", Path;
+    #[cfg(target_arch = "x86_64")]
+    SYS_chmod => "chmod", Path;
+    #[cfg(target_arch = "x86_64")]
+    SYS_chown => "chown", Path;
+    #[cfg(target_arch = "x86_64")]
+    SYS_lchown => "lchown", Path;
+    #[cfg(target_arch = "x86_64")]
+    SYS_utime => "utime", Path;
+    #[cfg(target_arch = "x86_64")]
+    SYS_utimes => "utimes", Path;
+    #[cfg(target_arch = "x86_64")]
+    SYS_mknod => "mknod", Path;
+    #[cfg(target_arch = "x86_64")]
+    SYS_unlink => "unlink", Path;
+    #[cfg(target_arch = "x86_64")]
+    SYS_open => "open", PathFlags;
+    #[cfg(target_arch = "x86_64")]
+    SYS_rename => "rename", TwoPaths;
+    #[cfg(target_arch = "x86_64")]
+    SYS_link => "link", TwoPaths;
+    #[cfg(target_arch = "x86_64")]
+    SYS_symlink => "symlink", TwoPaths;
+    #[cfg(target_arch = "x86_64")]
+    SYS_fork => "fork", NoArgs;
+    #[cfg(target_arch = "x86_64")]
+    SYS_vfork => "vfork", NoArgs;
+}
+
+/// The kernel's value; libc has 0 on 64-bit targets, where it is implied.
+#[cfg(target_arch = "x86_64")]
+const O_LARGEFILE: libc::c_int = 0o100000;
+#[cfg(target_arch = "aarch64")]
+const O_LARGEFILE: libc::c_int = 0o400000;
+
+/// Host values, which are not always the ones [`crate::flags::OpenFlags`]
+/// decodes numbers with.
+const OPEN_FLAGS: &[(libc::c_int, &str)] = &[
+    (libc::O_CREAT, "O_CREAT"),
+    (libc::O_EXCL, "O_EXCL"),
+    (libc::O_NOCTTY, "O_NOCTTY"),
+    (libc::O_TRUNC, "O_TRUNC"),
+    (libc::O_APPEND, "O_APPEND"),
+    (libc::O_NONBLOCK, "O_NONBLOCK"),
+    (libc::O_DSYNC, "O_DSYNC"),
+    (libc::O_DIRECT, "O_DIRECT"),
+    (O_LARGEFILE, "O_LARGEFILE"),
+    (libc::O_DIRECTORY, "O_DIRECTORY"),
+    (libc::O_NOFOLLOW, "O_NOFOLLOW"),
+    (libc::O_NOATIME, "O_NOATIME"),
+    (libc::O_CLOEXEC, "O_CLOEXEC"),
+    (libc::O_PATH, "O_PATH"),
+];
+
+const CLONE_FLAGS: &[(libc::c_int, &str)] = &[
+    (libc::CLONE_VM, "CLONE_VM"),
+    (libc::CLONE_FS, "CLONE_FS"),
+    (libc::CLONE_FILES, "CLONE_FILES"),
+    (libc::CLONE_SIGHAND, "CLONE_SIGHAND"),
+    (libc::CLONE_PIDFD, "CLONE_PIDFD"),
+    (libc::CLONE_PTRACE, "CLONE_PTRACE"),
+    (libc::CLONE_VFORK, "CLONE_VFORK"),
+    (libc::CLONE_PARENT, "CLONE_PARENT"),
+    (libc::CLONE_THREAD, "CLONE_THREAD"),
+    (libc::CLONE_NEWNS, "CLONE_NEWNS"),
+    (libc::CLONE_SYSVSEM, "CLONE_SYSVSEM"),
+    (libc::CLONE_SETTLS, "CLONE_SETTLS"),
+    (libc::CLONE_PARENT_SETTID, "CLONE_PARENT_SETTID"),
+    (libc::CLONE_CHILD_CLEARTID, "CLONE_CHILD_CLEARTID"),
+    (libc::CLONE_CHILD_SETTID, "CLONE_CHILD_SETTID"),
+];
+
+const FSCONFIG_CMDS: &[&str] = &[
+    "FSCONFIG_SET_FLAG",
+    "FSCONFIG_SET_STRING",
+    "FSCONFIG_SET_BINARY",
+    "FSCONFIG_SET_PATH",
+    "FSCONFIG_SET_PATH_EMPTY",
+    "FSCONFIG_SET_FD",
+    "FSCONFIG_CMD_CREATE",
+    "FSCONFIG_CMD_RECONFIGURE",
+    "FSCONFIG_CMD_CREATE_EXCL",
+];
+
+const SIGNALS: &[(libc::c_int, &str)] = &[
+    (libc::SIGHUP, "SIGHUP"),
+    (libc::SIGINT, "SIGINT"),
+    (libc::SIGQUIT, "SIGQUIT"),
+    (libc::SIGILL, "SIGILL"),
+    (libc::SIGTRAP, "SIGTRAP"),
+    (libc::SIGABRT, "SIGABRT"),
+    (libc::SIGBUS, "SIGBUS"),
+    (libc::SIGFPE, "SIGFPE"),
+    (libc::SIGKILL, "SIGKILL"),
+    (libc::SIGUSR1, "SIGUSR1"),
+    (libc::SIGSEGV, "SIGSEGV"),
+    (libc::SIGUSR2, "SIGUSR2"),
+    (libc::SIGPIPE, "SIGPIPE"),
+    (libc::SIGALRM, "SIGALRM"),
+    (libc::SIGTERM, "SIGTERM"),
+    (libc::SIGSTKFLT, "SIGSTKFLT"),
+    (libc::SIGCHLD, "SIGCHLD"),
+    (libc::SIGCONT, "SIGCONT"),
+    (libc::SIGSTOP, "SIGSTOP"),
+    (libc::SIGTSTP, "SIGTSTP"),
+    (libc::SIGTTIN, "SIGTTIN"),
+    (libc::SIGTTOU, "SIGTTOU"),
+    (libc::SIGURG, "SIGURG"),
+    (libc::SIGXCPU, "SIGXCPU"),
+    (libc::SIGXFSZ, "SIGXFSZ"),
+    (libc::SIGVTALRM, "SIGVTALRM"),
+    (libc::SIGPROF, "SIGPROF"),
+    (libc::SIGWINCH, "SIGWINCH"),
+    (libc::SIGIO, "SIGIO"),
+    (libc::SIGPWR, "SIGPWR"),
+    (libc::SIGSYS, "SIGSYS"),
+];
+
+/// `SIGSEGV`, or `SIG34` for signals without a name, as strace prints them.
+fn signal_name(sig: libc::c_int) -> String {
+    match SIGNALS.iter().find(|&&(n, _)| n == sig) {
+        Some(&(_, name)) => name.to_string(),
+        None => format!("SIG{sig}"),
+    }
+}
+
+/// strace's names for `si_code` values: the generic ones under signal 0,
+/// the rest under the signal they belong to.
+const SI_CODES: &[(libc::c_int, libc::c_int, &str)] = &[
+    (0, libc::SI_USER, "SI_USER"),
+    (0, libc::SI_KERNEL, "SI_KERNEL"),
+    (0, libc::SI_QUEUE, "SI_QUEUE"),
+    (0, libc::SI_TIMER, "SI_TIMER"),
+    (0, libc::SI_MESGQ, "SI_MESGQ"),
+    (0, libc::SI_ASYNCIO, "SI_ASYNCIO"),
+    (0, libc::SI_SIGIO, "SI_SIGIO"),
+    (0, libc::SI_TKILL, "SI_TKILL"),
+    (libc::SIGSEGV, 1, "SEGV_MAPERR"),
+    (libc::SIGSEGV, 2, "SEGV_ACCERR"),
+    (libc::SIGBUS, libc::BUS_ADRALN, "BUS_ADRALN"),
+    (libc::SIGBUS, libc::BUS_ADRERR, "BUS_ADRERR"),
+    (libc::SIGBUS, libc::BUS_OBJERR, "BUS_OBJERR"),
+    (libc::SIGILL, 1, "ILL_ILLOPC"),
+    (libc::SIGILL, 2, "ILL_ILLOPN"),
+    (libc::SIGILL, 3, "ILL_ILLADR"),
+    (libc::SIGILL, 4, "ILL_ILLTRP"),
+    (libc::SIGILL, 5, "ILL_PRVOPC"),
+    (libc::SIGILL, 6, "ILL_PRVREG"),
+    (libc::SIGILL, 7, "ILL_COPROC"),
+    (libc::SIGILL, 8, "ILL_BADSTK"),
+    (libc::SIGFPE, 1, "FPE_INTDIV"),
+    (libc::SIGFPE, 2, "FPE_INTOVF"),
+    (libc::SIGFPE, 3, "FPE_FLTDIV"),
+    (libc::SIGFPE, 4, "FPE_FLTOVF"),
+    (libc::SIGFPE, 5, "FPE_FLTUND"),
+    (libc::SIGFPE, 6, "FPE_FLTRES"),
+    (libc::SIGFPE, 7, "FPE_FLTINV"),
+    (libc::SIGFPE, 8, "FPE_FLTSUB"),
+    (libc::SIGTRAP, libc::TRAP_BRKPT, "TRAP_BRKPT"),
+    (libc::SIGTRAP, libc::TRAP_TRACE, "TRAP_TRACE"),
+    (libc::SIGCHLD, libc::CLD_EXITED, "CLD_EXITED"),
+    (libc::SIGCHLD, libc::CLD_KILLED, "CLD_KILLED"),
+    (libc::SIGCHLD, libc::CLD_DUMPED, "CLD_DUMPED"),
+    (libc::SIGCHLD, libc::CLD_TRAPPED, "CLD_TRAPPED"),
+    (libc::SIGCHLD, libc::CLD_STOPPED, "CLD_STOPPED"),
+    (libc::SIGCHLD, libc::CLD_CONTINUED, "CLD_CONTINUED"),
+];
+
+/// Whether `code` was set by a sender (`kill`, `sigqueue`, ...) rather
+/// than by the kernel for this particular signal.
+fn is_generic_code(code: libc::c_int) -> bool {
+    code <= 0 || code == libc::SI_KERNEL
+}
+
+fn render_si_code(sig: libc::c_int, code: libc::c_int) -> String {
+    let table_sig = if is_generic_code(code) { 0 } else { sig };
+    match SI_CODES.iter().find(|&&(s, c, _)| s == table_sig && c == code) {
+        Some(&(_, _, name)) => name.to_string(),
+        None => code.to_string(),
+    }
+}
+
+fn flag_parts(value: libc::c_int, table: &[(libc::c_int, &'static str)], mut parts: Vec<&'static str>) -> Arg<'static> {
+    let mut rest = value;
+    for &(bit, name) in table {
+        if value & bit == bit {
+            parts.push(name);
+            rest &= !bit;
+        }
+    }
+    if rest != 0 || parts.is_empty() {
+        parts.extend(bit_parts(rest as u32 as u64));
+    }
+    Arg::Flags(parts)
+}
+
+fn open_flags(flags: u64) -> Arg<'static> {
+    let flags = flags as libc::c_int;
+    let mode = match flags & libc::O_ACCMODE {
+        libc::O_RDONLY => "O_RDONLY",
+        libc::O_WRONLY => "O_WRONLY",
+        _ => "O_RDWR",
+    };
+    flag_parts(flags & !libc::O_ACCMODE, OPEN_FLAGS, vec![mode])
+}
+
+fn clone_flags(flags: u64) -> Arg<'static> {
+    let flags = flags as libc::c_int;
+    flag_parts(flags & !0xff, CLONE_FLAGS, vec![])
+}
+
+fn string(s: String) -> Arg<'static> {
+    Arg::Str { value: s, truncated: false }
+}
+
+/// A pointer, count or other raw number.
+fn int(v: u64) -> Arg<'static> {
+    Arg::Int(v as i64)
+}
+
+/// `fd<path>`, printed with the path escaped the way `strace -y` does.
+fn annotate(fd: FdNum, path: Option<String>) -> Arg<'static> {
+    Arg::Fd { fd, path: path.map(Cow::Owned) }
+}
+
+fn proc_link(pid: i32, what: &str) -> Option<String> {
+    fs::read_link(format!("/proc/{pid}/{what}"))
+        .ok()
+        .map(|p| p.to_string_lossy().into_owned())
+}
+
+/// An event reported by the [`Tracer`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceEvent {
+    /// A completed path-bearing syscall. `call` renders it like a strace
+    /// line; `args` are its decoded arguments, to classify with
+    /// [`crate::TraceParser::parse_call`].
+    Call { call: SyscallLine, args: Vec<Arg<'static>> },
+    /// A traced process went away.
+    Exited { pid: i32, status: ExitStatus },
+    /// A signal is being delivered to a traced process.
+    Signal { pid: i32, signal: Signal },
+}
+
+#[derive(Default)]
+struct Proc {
+    /// The syscall we are inside of, if it is one we report.
+    pending: Option<(&'static str, Vec<Arg<'static>>)>,
+}
+
+/// Runs a command under ptrace, following forks and clones, and yields the
+/// syscalls the parser classifies.
+pub struct Tracer {
+    root: i32,
+    procs: HashMap<i32, Proc>,
+    queue: VecDeque<TraceEvent>,
+    seq: usize,
+    root_status: Option<ExitStatus>,
+}
+
+/// Wait for `pid` to change state and return its status.
+fn waitpid(pid: i32, options: libc::c_int) -> io::Result<libc::c_int> {
+    let mut status = 0;
+    // SAFETY: `status` is a valid out-pointer.
+    if unsafe { libc::waitpid(pid, &mut status, options) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(status)
+}
+
+fn ptrace(req: libc::c_uint, pid: i32, addr: usize, data: usize) -> io::Result<libc::c_long> {
+    // SAFETY: every request we issue either ignores `addr`/`data` or passes
+    // a pointer to a live, correctly sized buffer.
+    let r = unsafe { libc::ptrace(req as _, pid, addr, data) };
+    if r == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(r)
+    }
+}
+
+impl Tracer {
+    /// Spawn `cmd` stopped under ptrace and start tracing it.
+    pub fn spawn(cmd: &mut Command) -> io::Result<Tracer> {
+        // SAFETY: PTRACE_TRACEME is async-signal-safe and touches no memory.
+        unsafe {
+            cmd.pre_exec(|| {
+                if libc::ptrace(libc::PTRACE_TRACEME, 0, 0, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let child = cmd.spawn()?;
+        let root = child.id() as i32;
+
+        // The child stops with SIGTRAP once the exec succeeds.
+        waitpid(root, libc::__WALL)?;
+
+        // PTRACE_TRACEME cannot tell a group-stop from a signal, nor keep a
+        // stopped task stopped, so swap it for PTRACE_SEIZE: detach into a
+        // plain SIGSTOP, seize the stopped child, and wake it with SIGCONT.
+        ptrace(libc::PTRACE_DETACH, root, 0, libc::SIGSTOP as usize)?;
+        waitpid(root, libc::WUNTRACED)?;
+        let options = libc::PTRACE_O_TRACESYSGOOD
+            | libc::PTRACE_O_TRACEFORK
+            | libc::PTRACE_O_TRACEVFORK
+            | libc::PTRACE_O_TRACECLONE
+            | libc::PTRACE_O_TRACEEXEC
+            | libc::PTRACE_O_EXITKILL;
+        ptrace(libc::PTRACE_SEIZE, root, 0, options as usize)?;
+        // SAFETY: plain syscall on our own child.
+        if unsafe { libc::kill(root, libc::SIGCONT) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        // Swallow our own SIGCONT; anything before it is the seize
+        // reporting the stop we just ended.
+        loop {
+            let status = waitpid(root, libc::__WALL)?;
+            if status >> 16 == 0 && libc::WSTOPSIG(status) == libc::SIGCONT {
+                break;
+            }
+            ptrace(libc::PTRACE_CONT, root, 0, 0)?;
+        }
+
+        let mut tracer = Tracer {
+            root,
+            procs: HashMap::new(),
+            queue: VecDeque::new(),
+            seq: 0,
+            root_status: None,
+        };
+        tracer.procs.insert(root, Proc::default());
+
+        // We attached after the initial execve; report it from /proc.
+        if let Some(exe) = proc_link(root, "exe") {
+            let cmdline = fs::read(format!("/proc/{root}/cmdline")).unwrap_or_default();
+            let argv = cmdline
+                .split(|&b| b == 0)
+                .filter(|a| !a.is_empty())
+                .map(|a| string(String::from_utf8_lossy(a).into_owned()))
+                .collect();
+            let args = vec![string(exe), Arg::Array(argv), Arg::Null];
+            tracer.push_call(root, "execve", args, "0".to_string());
+        }
+
+        ptrace(libc::PTRACE_SYSCALL, root, 0, 0)?;
+        Ok(tracer)
+    }
+
+    pub fn root(&self) -> i32 {
+        self.root
+    }
+
+    /// Exit status of the traced command, once it has exited.
+    pub fn root_status(&self) -> Option<&ExitStatus> {
+        self.root_status.as_ref()
+    }
+
+    fn push_call(&mut self, pid: i32, syscall: &str, args: Vec<Arg<'static>>, ret: String) {
+        self.seq += 1;
+        let call = SyscallLine {
+            line: self.seq,
+            pid,
+            syscall: syscall.to_string(),
+            args: args.iter().map(Arg::to_string).collect::<Vec<_>>().join(", "),
+            ret,
+        };
+        self.queue.push_back(TraceEvent::Call { call, args });
+    }
+
+    fn read_string(pid: i32, addr: u64) -> Arg<'static> {
+        match Self::read_cstr(pid, addr) {
+            Some(s) => string(s),
+            None => Arg::Null,
+        }
+    }
+
+    /// The NUL-terminated string at `addr`, unquoted; `None` for `NULL`.
+    fn read_cstr(pid: i32, addr: u64) -> Option<String> {
+        if addr == 0 {
+            return None;
+        }
+        let Ok(mem) = File::open(format!("/proc/{pid}/mem")) else {
+            return Some(String::new());
+        };
+        let mut bytes = Vec::new();
+        let mut buf = [0u8; 256];
+        while bytes.len() < libc::PATH_MAX as usize {
+            let n = match mem.read_at(&mut buf, addr + bytes.len() as u64) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            if let Some(end) = buf[..n].iter().position(|&b| b == 0) {
+                bytes.extend_from_slice(&buf[..end]);
+                break;
+            }
+            bytes.extend_from_slice(&buf[..n]);
+        }
+        Some(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    /// A `NULL`-terminated array of strings such as `argv`.
+    fn read_argv(pid: i32, addr: u64) -> Arg<'static> {
+        if addr == 0 {
+            return Arg::Null;
+        }
+        let Ok(mem) = File::open(format!("/proc/{pid}/mem")) else {
+            return int(addr);
+        };
+        let mut items = Vec::new();
+        for i in 0..MAX_ARGV {
+            let mut buf = [0u8; 8];
+            if mem.read_exact_at(&mut buf, addr + i * 8).is_err() {
+                break;
+            }
+            match u64::from_ne_bytes(buf) {
+                0 => return Arg::Array(items),
+                p => items.push(Self::read_string(pid, p)),
+            }
+        }
+        items.push(Arg::Truncated);
+        Arg::Array(items)
+    }
+
+    /// `struct open_how { u64 flags; u64 mode; u64 resolve; }`.
+    fn read_open_how(pid: i32, addr: u64) -> Arg<'static> {
+        let mut buf = [0u8; 24];
+        let read = File::open(format!("/proc/{pid}/mem")).and_then(|mem| mem.read_exact_at(&mut buf, addr));
+        if addr == 0 || read.is_err() {
+            return int(addr);
+        }
+        let field = |i: usize| u64::from_ne_bytes(buf[i * 8..i * 8 + 8].try_into().unwrap());
+        Arg::Struct(vec![
+            (Some("flags"), open_flags(field(0))),
+            (Some("mode"), int(field(1))),
+            (Some("resolve"), Arg::Flags(ResolveFlags(field(2)).parts())),
+        ])
+    }
+
+    /// What the kernel says about the signal `pid` is stopped with; just
+    /// its name if that is not available. `None` if `pid` is in a
+    /// group-stop rather than having a signal delivered.
+    fn read_siginfo(pid: i32, sig: libc::c_int) -> Option<Signal> {
+        let mut signal = Signal { signal: signal_name(sig), code: None, sender_pid: None, sender_uid: None, addr: None };
+        // SAFETY: siginfo_t is plain data, and all zeroes is a valid value.
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        if let Err(e) = ptrace(libc::PTRACE_GETSIGINFO, pid, 0, &mut info as *mut _ as usize) {
+            return (e.raw_os_error() != Some(libc::EINVAL)).then_some(signal);
+        }
+        let code = info.si_code;
+        signal.code = Some(render_si_code(sig, code));
+        // SAFETY: each union member is only read for the signals and codes
+        // the kernel fills it in for.
+        unsafe {
+            if code <= 0 || sig == libc::SIGCHLD {
+                signal.sender_pid = Some(info.si_pid());
+                signal.sender_uid = Some(info.si_uid());
+            } else if matches!(sig, libc::SIGSEGV | libc::SIGBUS | libc::SIGILL | libc::SIGFPE | libc::SIGTRAP) {
+                let addr = info.si_addr() as usize;
+                signal.addr = Some(if addr == 0 { "NULL".to_string() } else { format!("{addr:#x}") });
+            }
+        }
+        Some(signal)
+    }
+
+    /// `clone3`'s `struct clone_args`, of which only `flags` (the first
+    /// field) matters here.
+    fn read_clone_args(pid: i32, addr: u64) -> Arg<'static> {
+        let mut buf = [0u8; 8];
+        let read = File::open(format!("/proc/{pid}/mem")).and_then(|mem| mem.read_exact_at(&mut buf, addr));
+        if addr == 0 || read.is_err() {
+            return int(addr);
+        }
+        let flags = Arg::Flags(CloneFlags(u64::from_ne_bytes(buf)).parts());
+        Arg::Struct(vec![(Some("flags"), flags), (None, Arg::Truncated)])
+    }
+
+    /// `struct sockaddr *`; only an `AF_UNIX` address has a path in it.
+    /// Abstract names are left out, as they name no file.
+    fn read_sockaddr(pid: i32, addr: u64, len: u64) -> Arg<'static> {
+        let mut buf = vec![0u8; len.min(std::mem::size_of::<libc::sockaddr_un>() as u64) as usize];
+        let read = File::open(format!("/proc/{pid}/mem")).and_then(|mem| mem.read_exact_at(&mut buf, addr));
+        if addr == 0 || buf.len() < 2 || read.is_err() {
+            return int(addr);
+        }
+        let family = u16::from_ne_bytes([buf[0], buf[1]]);
+        if family != libc::AF_UNIX as u16 {
+            return Arg::Struct(vec![(Some("sa_family"), int(family.into())), (None, Arg::Truncated)]);
+        }
+        let unix = (Some("sa_family"), Arg::Flags(vec!["AF_UNIX"]));
+        match &buf[2..] {
+            [0, name @ ..] if !name.is_empty() => Arg::Struct(vec![unix, (None, Arg::Truncated)]),
+            path => {
+                let end = path.iter().position(|&b| b == 0).unwrap_or(path.len());
+                let path = string(String::from_utf8_lossy(&path[..end]).into_owned());
+                Arg::Struct(vec![unix, (Some("sun_path"), path)])
+            }
+        }
+    }
+
+    /// `fsconfig`'s `value` and `aux`, which mean different things per `cmd`.
+    fn read_fsconfig(pid: i32, a: &[u64]) -> Vec<Arg<'static>> {
+        let cmd = FSCONFIG_CMDS.get(a[1] as usize).copied();
+        let mut parts = vec![
+            Self::read_fd(pid, a[0]),
+            cmd.map_or_else(|| int(a[1]), |c| Arg::Flags(vec![c])),
+            Self::read_string(pid, a[2]),
+        ];
+        match cmd {
+            Some("FSCONFIG_SET_STRING") => parts.extend([Self::read_string(pid, a[3]), int(a[4])]),
+            Some("FSCONFIG_SET_PATH" | "FSCONFIG_SET_PATH_EMPTY") => {
+                parts.extend([Self::read_string(pid, a[3]), Self::read_fd(pid, a[4])])
+            }
+            _ => parts.extend([int(a[3]), int(a[4])]),
+        }
+        parts
+    }
+
+    /// A first-path argument, made absolute against the tracee's cwd.
+    fn read_path(pid: i32, addr: u64) -> Arg<'static> {
+        let Some(path) = Self::read_cstr(pid, addr) else {
+            return Arg::Null;
+        };
+        match proc_link(pid, "cwd") {
+            Some(cwd) if !path.is_empty() && !path.starts_with('/') => {
+                let sep = if cwd.ends_with('/') { "" } else { "/" };
+                string(format!("{cwd}{sep}{path}"))
+            }
+            _ => string(path),
+        }
+    }
+
+    fn read_fd(pid: i32, fd: u64) -> Arg<'static> {
+        let fd = fd as i32;
+        if fd == libc::AT_FDCWD {
+            return annotate(FdNum::AtFdcwd, proc_link(pid, "cwd"));
+        }
+        match proc_link(pid, &format!("fd/{fd}")) {
+            Some(path) => annotate(FdNum::Num(fd), Some(path)),
+            None => Arg::Int(fd.into()),
+        }
+    }
+
+    fn decode_args(pid: i32, layout: Layout, a: &[u64]) -> Vec<Arg<'static>> {
+        match layout {
+            Layout::Path => vec![Self::read_path(pid, a[0])],
+            Layout::PathFlags => vec![Self::read_path(pid, a[0]), open_flags(a[1])],
+            Layout::Exec => vec![Self::read_path(pid, a[0]), Self::read_argv(pid, a[1]), int(a[2])],
+            Layout::FdPathExec => vec![
+                Self::read_fd(pid, a[0]),
+                Self::read_string(pid, a[1]),
+                Self::read_argv(pid, a[2]),
+                int(a[3]),
+                int(a[4]),
+            ],
+            Layout::TwoPaths => vec![Self::read_path(pid, a[0]), Self::read_path(pid, a[1])],
+            Layout::PathFdPath => vec![
+                Self::read_string(pid, a[0]),
+                Self::read_fd(pid, a[1]),
+                Self::read_string(pid, a[2]),
+            ],
+            Layout::FdPath => vec![
+                Self::read_fd(pid, a[0]),
+                Self::read_string(pid, a[1]),
+                int(a[2]),
+                int(a[3]),
+                int(a[4]),
+            ],
+            Layout::FdPathFlags => vec![
+                Self::read_fd(pid, a[0]),
+                Self::read_string(pid, a[1]),
+                open_flags(a[2]),
+            ],
+            Layout::FdPathHow => vec![
+                Self::read_fd(pid, a[0]),
+                Self::read_string(pid, a[1]),
+                Self::read_open_how(pid, a[2]),
+                int(a[3]),
+            ],
+            Layout::FdPathFdPath => vec![
+                Self::read_fd(pid, a[0]),
+                Self::read_string(pid, a[1]),
+                Self::read_fd(pid, a[2]),
+                Self::read_string(pid, a[3]),
+                int(a[4]),
+            ],
+            Layout::Fd => vec![Self::read_fd(pid, a[0]), int(a[1]), int(a[2])],
+            Layout::FdFd => vec![Self::read_fd(pid, a[0]), Self::read_fd(pid, a[1]), int(a[2])],
+            Layout::FdOffFd => vec![
+                Self::read_fd(pid, a[0]),
+                int(a[1]),
+                Self::read_fd(pid, a[2]),
+                int(a[3]),
+                int(a[4]),
+            ],
+            Layout::Mmap => vec![
+                int(a[0]),
+                int(a[1]),
+                Arg::Flags(ProtFlags(a[2]).parts()),
+                Arg::Flags(MapFlags(a[3]).parts()),
+                Self::read_fd(pid, a[4]),
+                int(a[5]),
+            ],
+            Layout::Mount => vec![
+                Self::read_string(pid, a[0]),
+                Self::read_path(pid, a[1]),
+                Self::read_string(pid, a[2]),
+                int(a[3]),
+                int(a[4]),
+            ],
+            Layout::FanotifyMark => vec![
+                Self::read_fd(pid, a[0]),
+                int(a[1]),
+                int(a[2]),
+                Self::read_fd(pid, a[3]),
+                Self::read_string(pid, a[4]),
+            ],
+            Layout::Handle => vec![Self::read_fd(pid, a[0]), int(a[1]), open_flags(a[2])],
+            Layout::Quotactl => vec![
+                int(a[0]),
+                Self::read_path(pid, a[1]),
+                int(a[2]),
+                int(a[3]),
+            ],
+            Layout::Fsconfig => Self::read_fsconfig(pid, a),
+            Layout::Sockaddr => vec![Self::read_fd(pid, a[0]), Self::read_sockaddr(pid, a[1], a[2]), int(a[2])],
+            Layout::Clone => vec![
+                Arg::Named("child_stack", Box::new(int(a[1]))),
+                Arg::Named("flags", Box::new(clone_flags(a[0]))),
+            ],
+            Layout::Clone3 => vec![Self::read_clone_args(pid, a[0]), int(a[1])],
+            Layout::Unshare => vec![Arg::Flags(CloneFlags(a[0]).parts())],
+            Layout::NoArgs => vec![],
+        }
+    }
+
+    fn render_ret(pid: i32, syscall: &str, sval: i64, is_error: bool) -> String {
+        if is_error {
+            let err = io::Error::from_raw_os_error(-sval as i32);
+            return format!("-1 ({err})");
+        }
+        match syscall {
+            "open" | "openat" | "openat2" | "open_tree" | "open_by_handle_at" => match proc_link(pid, &format!("fd/{sval}")) {
+                Some(path) => annotate(FdNum::Num(sval as i32), Some(path)).to_string(),
+                None => sval.to_string(),
+            },
+            _ => sval.to_string(),
+        }
+    }
+
+    fn syscall_stop(&mut self, pid: i32) -> io::Result<()> {
+        let mut info = SyscallInfo::default();
+        ptrace(
+            PTRACE_GET_SYSCALL_INFO,
+            pid,
+            std::mem::size_of::<SyscallInfo>(),
+            &mut info as *mut SyscallInfo as usize,
+        )?;
+        match info.op {
+            PTRACE_SYSCALL_INFO_ENTRY => {
+                self.procs.entry(pid).or_default().pending =
+                    lookup(info.data[0]).map(|(name, layout)| (name, Self::decode_args(pid, layout, &info.data[1..7])));
+            }
+            PTRACE_SYSCALL_INFO_EXIT => {
+                let pending = self.procs.get_mut(&pid).and_then(|p| p.pending.take());
+                if let Some((name, args)) = pending {
+                    let sval = info.data[0] as i64;
+                    let is_error = info.data[1] as u8 != 0;
+                    let ret = Self::render_ret(pid, name, sval, is_error);
+                    self.push_call(pid, name, args, ret);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Wait for the next stop and handle it, queueing any events.
+    fn step(&mut self) -> io::Result<()> {
+        let mut status = 0;
+        // SAFETY: `status` is a valid out-pointer.
+        let pid = unsafe { libc::waitpid(-1, &mut status, libc::__WALL) };
+        if pid == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        self.procs.entry(pid).or_default();
+
+        if libc::WIFEXITED(status) || libc::WIFSIGNALED(status) {
+            self.procs.remove(&pid);
+            let status = if libc::WIFEXITED(status) {
+                ExitStatus::exited(libc::WEXITSTATUS(status))
+            } else {
+                ExitStatus::killed(Some(signal_name(libc::WTERMSIG(status))), libc::WCOREDUMP(status))
+            };
+            if pid == self.root {
+                self.root_status = Some(status.clone());
+            }
+            self.queue.push_back(TraceEvent::Exited { pid, status });
+            return Ok(());
+        }
+        if !libc::WIFSTOPPED(status) {
+            return Ok(());
+        }
+
+        let sig = libc::WSTOPSIG(status);
+        let event = status >> 16;
+        let mut inject = 0;
+        if sig == libc::SIGTRAP | 0x80 {
+            self.syscall_stop(pid)?;
+        } else if event == libc::PTRACE_EVENT_STOP {
+            // A group-stop stays stopped until SIGCONT or SIGKILL, which
+            // PTRACE_LISTEN reports as another PTRACE_EVENT_STOP. Anything
+            // else is a new child's first stop or the end of a group-stop.
+            if matches!(sig, libc::SIGSTOP | libc::SIGTSTP | libc::SIGTTIN | libc::SIGTTOU) {
+                return match ptrace(libc::PTRACE_LISTEN, pid, 0, 0) {
+                    Err(e) if e.raw_os_error() == Some(libc::ESRCH) => Ok(()),
+                    r => r.map(|_| ()),
+                };
+            }
+        } else if event != 0 {
+            if matches!(event, libc::PTRACE_EVENT_FORK | libc::PTRACE_EVENT_VFORK | libc::PTRACE_EVENT_CLONE) {
+                let mut child: libc::c_ulong = 0;
+                ptrace(libc::PTRACE_GETEVENTMSG, pid, 0, &mut child as *mut _ as usize)?;
+                self.procs.entry(child as i32).or_default();
+            }
+        } else if let Some(signal) = Self::read_siginfo(pid, sig) {
+            self.queue.push_back(TraceEvent::Signal { pid, signal });
+            inject = sig;
+        }
+
+        // The tracee may have been killed in the meantime.
+        match ptrace(libc::PTRACE_SYSCALL, pid, 0, inject as usize) {
+            Err(e) if e.raw_os_error() == Some(libc::ESRCH) => Ok(()),
+            r => r.map(|_| ()),
+        }
+    }
+}
+
+impl Iterator for Tracer {
+    type Item = io::Result<TraceEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(e) = self.queue.pop_front() {
+                return Some(Ok(e));
+            }
+            if self.procs.is_empty() {
+                return None;
+            }
+            match self.step() {
+                Ok(()) => {}
+                Err(e) if e.raw_os_error() == Some(libc::ECHILD) => self.procs.clear(),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Record, TraceParser};
+
+    /// Every event of running `sh -c script`, with `args` as `$1...`.
+    fn trace_sh(script: &str, args: &[&str]) -> Vec<TraceEvent> {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(script).arg("sh").args(args);
+        Tracer::spawn(&mut cmd).unwrap().collect::<io::Result<_>>().unwrap()
+    }
+
+    /// The paths the parser gets out of the calls among `events`.
+    fn paths(events: &[TraceEvent]) -> Vec<String> {
+        let mut parser = TraceParser::with_cwd("/");
+        let mut paths = Vec::new();
+        for e in events {
+            if let TraceEvent::Call { call, args } = e
+                && let Ok(Record::Call { files: Some(files), .. }) = parser.parse_call(call.clone(), args)
+            {
+                paths.extend(files.iter().map(|f| f.fname().to_string()));
+            }
+        }
+        paths
+    }
+
+    #[test]
+    fn odd_path_bytes_survive_the_annotation() {
+        let root = std::env::temp_dir().join(format!("strace-parse-tracer-{}", std::process::id()));
+        let dir = root.join("a\\x41>b\nc");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("f"), "x").unwrap();
+        let dir_str = dir.to_str().unwrap();
+
+        let events = trace_sh(r#"cd "$1" && cat f > /dev/null && cat "$1/f" > /dev/null"#, &[dir_str]);
+        let paths = paths(&events);
+        fs::remove_dir_all(&root).unwrap();
+
+        let file = format!("{dir_str}/f");
+        assert!(paths.contains(&file), "{file:?} not in {paths:?}");
+        let root_str = root.to_str().unwrap();
+        for p in paths.iter().filter(|p| p.starts_with(root_str)) {
+            assert!(p == root_str || p == dir_str || *p == file, "made-up path {p:?}");
+        }
+    }
+
+    #[test]
+    fn open_flags_use_the_host_values() {
+        let events = trace_sh("ls / > /dev/null", &[]);
+        let dir_open = events.iter().find_map(|e| match e {
+            TraceEvent::Call { call, args } if call.syscall == "openat" && args[1].as_str() == Some("/") => Some(&args[2]),
+            _ => None,
+        });
+        let flags = dir_open.and_then(Arg::flags).expect("ls opens / with flags");
+        assert!(flags.contains(&"O_DIRECTORY"), "{flags:?}");
+        assert!(!flags.contains(&"O_LARGEFILE"), "{flags:?}");
+    }
+
+    #[test]
+    fn a_stopped_child_is_one_signal() {
+        let events = trace_sh("(sleep 0.2; kill -CONT $$) & kill -STOP $$; echo after > /dev/null", &[]);
+        let stops: Vec<_> = events
+            .iter()
+            .filter(|e| matches!(e, TraceEvent::Signal { signal, .. } if signal.signal == "SIGSTOP"))
+            .collect();
+        assert_eq!(stops.len(), 1, "{stops:?}");
+    }
+}